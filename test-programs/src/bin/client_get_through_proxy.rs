@@ -0,0 +1,18 @@
+use waki::{Client, Proxy};
+
+fn main() {
+    let resp = Client::new()
+        .proxy(Proxy::from_env())
+        .get("http://example.com/path?a=b")
+        .send()
+        .unwrap();
+    assert_eq!(resp.status_code(), 200);
+    assert_eq!(resp.body().unwrap(), b"ok");
+
+    // https requests can't be tunneled through the proxy
+    let result = Client::new()
+        .proxy(Proxy::all(&std::env::var("HTTP_PROXY").unwrap()).unwrap())
+        .get("https://example.com/")
+        .send();
+    assert!(result.is_err_and(|e| e.to_string().contains("CONNECT")));
+}
@@ -0,0 +1,12 @@
+use waki::{handler, BodyTooLarge, ErrorCode, Request, Response};
+
+#[handler]
+fn hello(req: Request) -> Result<Response, ErrorCode> {
+    let body = req
+        .body_limited(10)
+        .map_err(|e| e.downcast::<BodyTooLarge>().unwrap())?;
+    Response::builder().body(body).build()
+}
+
+// required since this file is built as a `bin`
+fn main() {}
@@ -0,0 +1,11 @@
+use waki::{embed_dir, handler, ErrorCode, Request, Response};
+
+#[handler]
+fn hello(req: Request) -> Result<Response, ErrorCode> {
+    embed_dir!("../waki/tests/all/fixtures/static")
+        .strip_prefix("/static")
+        .serve(&req)
+}
+
+// required since this file is built as a `bin`
+fn main() {}
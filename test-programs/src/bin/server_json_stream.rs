@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+use waki::{handler, ErrorCode, Request, Response};
+
+#[derive(Deserialize, Serialize)]
+struct Item {
+    value: i32,
+}
+
+#[handler]
+fn hello(req: Request) -> Result<Response, ErrorCode> {
+    let ndjson = req.path() == "/ndjson";
+    let items = req
+        .json_array::<Item>()
+        .map(|item| Item {
+            value: item.unwrap().value * 2,
+        })
+        .collect::<Vec<_>>();
+    let builder = Response::builder();
+    if ndjson {
+        builder.ndjson(items).build()
+    } else {
+        builder.json_array(items).build()
+    }
+}
+
+// required since this file is built as a `bin`
+fn main() {}
@@ -0,0 +1,34 @@
+use waki::{
+    handler,
+    header::{HeaderMap, HeaderValue, LINK},
+    ErrorCode, Request, Response,
+};
+
+#[handler]
+fn hello(req: Request) -> Result<Response, ErrorCode> {
+    // echo every value of the request header, in order
+    let tags = req
+        .headers()
+        .get_all("x-tag")
+        .iter()
+        .map(|v| v.to_str().unwrap())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut links = HeaderMap::new();
+    links.append(LINK, HeaderValue::from_static("</style.css>; rel=preload"));
+    links.append(LINK, HeaderValue::from_static("</app.js>; rel=preload"));
+
+    Response::builder()
+        .append_header("Set-Cookie", "a=1")
+        .append_header("Set-Cookie", "b=2")
+        .header("X-Removed", "1")
+        .remove_header("X-Removed")
+        .header(LINK, "</old.css>; rel=preload")
+        .header_map(links)
+        .body(tags)
+        .build()
+}
+
+// required since this file is built as a `bin`
+fn main() {}
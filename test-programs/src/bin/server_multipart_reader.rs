@@ -0,0 +1,15 @@
+use std::io::Read;
+use waki::{handler, ErrorCode, Request, Response};
+
+#[handler]
+fn hello(req: Request) -> Result<Response, ErrorCode> {
+    let mut multipart = req.multipart_reader().unwrap();
+    let mut body = vec![];
+    while let Some(mut field) = multipart.next_field().unwrap() {
+        field.read_to_end(&mut body).unwrap();
+    }
+    Response::builder().body(body).build()
+}
+
+// required since this file is built as a `bin`
+fn main() {}
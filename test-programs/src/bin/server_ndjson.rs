@@ -0,0 +1,16 @@
+use serde::Deserialize;
+use waki::{handler, ErrorCode, Request, Response};
+
+#[derive(Deserialize)]
+struct Item {
+    value: i32,
+}
+
+#[handler]
+fn hello(req: Request) -> Result<Response, ErrorCode> {
+    let sum: i32 = req.ndjson::<Item>().map(|item| item.unwrap().value).sum();
+    Response::builder().body(sum.to_string()).build()
+}
+
+// required since this file is built as a `bin`
+fn main() {}
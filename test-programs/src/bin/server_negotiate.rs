@@ -0,0 +1,24 @@
+use waki::{handler, mime, ErrorCode, Request, Response};
+
+#[handler]
+fn hello(req: Request) -> Result<Response, ErrorCode> {
+    let mime = match req.negotiate(&[mime::APPLICATION_JSON, mime::TEXT_HTML]) {
+        Ok(mime) => mime,
+        Err(e) => return Ok(e.into()),
+    };
+    let lang = req.negotiate_language(&["en", "fr"]).unwrap_or("en");
+    let body = match (mime.subtype().as_str(), lang) {
+        ("json", "fr") => r#"{"message": "Bonjour"}"#,
+        ("json", _) => r#"{"message": "Hello"}"#,
+        (_, "fr") => "<p>Bonjour</p>",
+        _ => "<p>Hello</p>",
+    };
+    Response::builder()
+        .header("Content-Type", mime.as_ref())
+        .header("Content-Language", lang)
+        .body(body)
+        .build()
+}
+
+// required since this file is built as a `bin`
+fn main() {}
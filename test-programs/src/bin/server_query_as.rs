@@ -0,0 +1,33 @@
+use serde::Deserialize;
+use waki::{handler, ErrorCode, Request, Response};
+
+#[derive(Deserialize)]
+struct Query {
+    name: String,
+    tag: Vec<String>,
+    page: Option<u32>,
+}
+
+#[handler]
+fn hello(req: Request) -> Result<Response, ErrorCode> {
+    let query: Query = match req.query_as() {
+        Ok(query) => query,
+        Err(e) => {
+            return Response::builder()
+                .status_code(400)
+                .body(e.to_string())
+                .build()
+        }
+    };
+    Response::builder()
+        .body(format!(
+            "{} {:?} {}",
+            query.name,
+            query.tag,
+            query.page.unwrap_or(1)
+        ))
+        .build()
+}
+
+// required since this file is built as a `bin`
+fn main() {}
@@ -0,0 +1,12 @@
+use waki::{fs::ServeDir, handler, ErrorCode, Request, Response};
+
+#[handler]
+fn hello(req: Request) -> Result<Response, ErrorCode> {
+    ServeDir::new("static")
+        .strip_prefix("/static")
+        .directory_listing(true)
+        .serve(&req)
+}
+
+// required since this file is built as a `bin`
+fn main() {}
@@ -0,0 +1,18 @@
+use std::io::Read;
+use waki::{handler, ErrorCode, Request, Response};
+
+#[handler]
+fn hello(mut req: Request) -> Result<Response, ErrorCode> {
+    let mut body = vec![];
+    req.read_to_end(&mut body).unwrap();
+    let trailers = req.trailers().unwrap().unwrap();
+    let checksum = trailers.get("checksum").unwrap().clone();
+
+    Response::builder()
+        .body(body)
+        .trailers([("checksum", checksum)])
+        .build()
+}
+
+// required since this file is built as a `bin`
+fn main() {}
@@ -0,0 +1,125 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use std::fs;
+use std::path::{Path, PathBuf};
+use syn::{Error, LitStr, Result};
+
+// the extensions of pre-compressed variants, with their content coding
+const ENCODINGS: [(&str, &str); 2] = [("br", "br"), ("gz", "gzip")];
+
+struct File {
+    path: String,
+    source: PathBuf,
+    variants: Vec<(&'static str, PathBuf)>,
+}
+
+pub fn embed_dir(input: LitStr) -> Result<TokenStream> {
+    let span = input.span();
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR")
+        .map_err(|_| Error::new(span, "CARGO_MANIFEST_DIR is not set"))?;
+    let root = Path::new(&manifest_dir).join(input.value());
+    if !root.is_dir() {
+        return Err(Error::new(
+            span,
+            format!("{} is not a directory", root.display()),
+        ));
+    }
+
+    let mut sources = vec![];
+    walk(&root, "", &mut sources).map_err(|e| Error::new(span, e))?;
+    sources.sort();
+
+    // `name.br` and `name.gz` are embedded as variants of `name` if it exists, since the paths
+    // are sorted the file is always pushed before its variants
+    let mut files: Vec<File> = vec![];
+    for (path, source) in &sources {
+        let variant = ENCODINGS.iter().find_map(|(ext, encoding)| {
+            let base = path.strip_suffix(ext)?.strip_suffix('.')?;
+            let i = files.iter().position(|file| file.path == base)?;
+            Some((i, *encoding))
+        });
+        match variant {
+            Some((i, encoding)) => files[i].variants.push((encoding, source.clone())),
+            None => files.push(File {
+                path: path.clone(),
+                source: source.clone(),
+                variants: vec![],
+            }),
+        }
+    }
+
+    let files = files
+        .iter()
+        .map(|file| expand_file(file, span))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(quote! {
+        {
+            static FILES: &[::waki::fs::EmbeddedFile] = &[#(#files),*];
+            ::waki::fs::EmbeddedDir::new(FILES)
+        }
+    })
+}
+
+fn expand_file(file: &File, span: Span) -> Result<TokenStream> {
+    let path = &file.path;
+    let mime = mime_guess::from_path(path)
+        .first_or_octet_stream()
+        .to_string();
+    let (content, etag) = include(&file.source, span)?;
+    let variants = file
+        .variants
+        .iter()
+        .map(|(encoding, source)| {
+            let (content, etag) = include(source, span)?;
+            Ok(quote! {
+                ::waki::fs::EmbeddedVariant::new(#encoding, #etag, #content)
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(quote! {
+        ::waki::fs::EmbeddedFile::new(#path, #mime, #etag, #content, &[#(#variants),*])
+    })
+}
+
+/// Include the content of the file, and compute its strong entity tag.
+fn include(source: &Path, span: Span) -> Result<(TokenStream, String)> {
+    let data = fs::read(source)
+        .map_err(|e| Error::new(span, format!("failed to read {}: {}", source.display(), e)))?;
+    // the same format as `EntityTag::from_content`
+    let etag = format!("{:x}-{:016x}", data.len(), fnv1a(&data));
+    let source = source
+        .to_str()
+        .ok_or_else(|| Error::new(span, format!("non UTF-8 path: {}", source.display())))?;
+    // `include_bytes` makes the build depend on the file
+    Ok((quote!(include_bytes!(#source)), etag))
+}
+
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn walk(
+    dir: &Path,
+    prefix: &str,
+    files: &mut Vec<(String, PathBuf)>,
+) -> std::result::Result<(), String> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| format!("failed to read {}: {}", dir.display(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("failed to read {}: {}", dir.display(), e))?;
+        let name = entry
+            .file_name()
+            .into_string()
+            .map_err(|name| format!("non UTF-8 file name: {:?}", name))?;
+        let path = format!("{}{}", prefix, name);
+        let source = entry.path();
+        if source.is_dir() {
+            walk(&source, &format!("{}/", path), files)?;
+        } else {
+            files.push((path, source));
+        }
+    }
+    Ok(())
+}
@@ -19,7 +19,7 @@ pub fn handler(input: ItemFn) -> Result<TokenStream> {
                 match request.try_into() {
                     Ok(req) => match #fn_name(req) {
                         Ok(resp) => ::waki::handle_response(response_out, resp),
-                        Err(e) => ::waki::bindings::wasi::http::types::ResponseOutparam::set(response_out, Err(e)),
+                        Err(e) => ::waki::handle_error(response_out, e),
                     }
                     Err(e) => ::waki::bindings::wasi::http::types::ResponseOutparam::set(response_out, Err(e)),
                 }
@@ -1,4 +1,6 @@
 mod dummy;
+#[cfg(feature = "embed")]
+mod embed;
 mod export;
 
 use proc_macro::TokenStream;
@@ -10,3 +12,11 @@ pub fn handler(_: TokenStream, input: TokenStream) -> TokenStream {
         .unwrap_or_else(syn::Error::into_compile_error)
         .into()
 }
+
+#[cfg(feature = "embed")]
+#[proc_macro]
+pub fn embed_dir(input: TokenStream) -> TokenStream {
+    embed::embed_dir(parse_macro_input!(input as syn::LitStr))
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -1,23 +1,100 @@
-use crate::bindings::wasi::{
-    http::types::{IncomingBody, InputStream, OutgoingBody},
-    io::streams::StreamError,
+use crate::{
+    bindings::wasi::{
+        http::types::{IncomingBody, InputStream, OutgoingBody},
+        io::streams::{OutputStream, StreamError},
+    },
+    header::HeaderMap,
+    ErrorCode,
 };
 
 use anyhow::{anyhow, Result};
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const CHUNK_SIZE: u64 = 1024 * 1024;
+
+static BODY_LIMIT: AtomicU64 = AtomicU64::new(u64::MAX);
+
+/// Set the default maximum body size in bytes, used when reading a whole body with methods
+/// such as `body()`, `form()`, `json()` or `multipart()`.
+///
+/// Default value: unlimited.
+///
+/// ```
+/// // Limit bodies to 10 MiB
+/// waki::set_body_limit(10 << 20);
+/// ```
+#[inline]
+pub fn set_body_limit(limit: u64) {
+    BODY_LIMIT.store(limit, Ordering::Relaxed);
+}
+
+#[inline]
+pub(crate) fn body_limit() -> u64 {
+    BODY_LIMIT.load(Ordering::Relaxed)
+}
+
+/// The error returned when a body exceeds the size limit.
+///
+/// It can be converted into an [`ErrorCode`], which is answered with a
+/// `413 Payload Too Large` response when returned from a handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BodyTooLarge {
+    limit: u64,
+}
+
+impl BodyTooLarge {
+    #[inline]
+    pub(crate) fn new(limit: u64) -> Self {
+        Self { limit }
+    }
+
+    /// Get the size limit that was exceeded.
+    #[inline]
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+}
+
+impl fmt::Display for BodyTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "body exceeds the size limit of {} bytes", self.limit)
+    }
+}
+
+impl std::error::Error for BodyTooLarge {}
+
+impl From<BodyTooLarge> for ErrorCode {
+    #[inline]
+    fn from(e: BodyTooLarge) -> Self {
+        ErrorCode::HttpRequestBodySize(Some(e.limit))
+    }
+}
 
 pub struct IncomingBodyStream {
+    // data that has been read from the stream by `fill_buf` but not consumed yet
+    buffer: RefCell<Buffer>,
     // input-stream resource is a child: it must be dropped before the parent incoming-body is dropped
     input_stream: InputStream,
-    _incoming_body: IncomingBody,
+    incoming_body: IncomingBody,
+}
+
+#[derive(Default)]
+struct Buffer {
+    data: Vec<u8>,
+    pos: usize,
 }
 
 impl From<IncomingBody> for IncomingBodyStream {
     #[inline]
     fn from(body: IncomingBody) -> Self {
         Self {
+            buffer: Default::default(),
             // The stream() method can only be called once
             input_stream: body.stream().unwrap(),
-            _incoming_body: body,
+            incoming_body: body,
         }
     }
 }
@@ -32,43 +109,206 @@ impl InputStream {
     }
 }
 
+impl IncomingBodyStream {
+    fn chunk(&self, len: u64) -> Result<Option<Vec<u8>>> {
+        let mut buffer = self.buffer.borrow_mut();
+        if buffer.pos < buffer.data.len() {
+            let end = buffer.data.len().min(buffer.pos + len as usize);
+            let chunk = buffer.data[buffer.pos..end].to_vec();
+            buffer.pos = end;
+            return Ok(Some(chunk));
+        }
+        self.input_stream.chunk(len)
+    }
+
+    fn finish(self) -> Result<Option<HeaderMap>> {
+        drop(self.input_stream);
+        let future_trailers = IncomingBody::finish(self.incoming_body);
+        future_trailers.subscribe().block();
+
+        match future_trailers.get() {
+            Some(Ok(Ok(Some(trailers)))) => Ok(Some(trailers.to_header_map()?)),
+            Some(Ok(Ok(None))) => Ok(None),
+            Some(Ok(Err(e))) => Err(e.into()),
+            Some(Err(())) => Err(anyhow!("trailers already taken")),
+            None => Err(anyhow!("trailers not available")),
+        }
+    }
+}
+
+impl Read for IncomingBodyStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl BufRead for IncomingBodyStream {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let buffer = self.buffer.get_mut();
+        if buffer.pos >= buffer.data.len() {
+            buffer.data = self
+                .input_stream
+                .chunk(CHUNK_SIZE)
+                .map_err(io::Error::other)?
+                .unwrap_or_default();
+            buffer.pos = 0;
+        }
+        Ok(&buffer.data[buffer.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        let buffer = self.buffer.get_mut();
+        buffer.pos = buffer.data.len().min(buffer.pos + amt);
+    }
+}
+
 pub enum Body {
-    Bytes(Vec<u8>),
+    Bytes(Cursor<Vec<u8>>),
     Stream(IncomingBodyStream),
+    // an outgoing body that is read while being sent
+    Reader(BufReader<Box<dyn Read>>),
+}
+
+impl From<Vec<u8>> for Body {
+    #[inline]
+    fn from(data: Vec<u8>) -> Self {
+        Body::Bytes(Cursor::new(data))
+    }
 }
 
 impl Body {
+    #[inline]
+    pub fn reader<R: Read + 'static>(reader: R) -> Self {
+        Body::Reader(BufReader::new(Box::new(reader)))
+    }
+
     #[inline]
     pub fn chunk(&self, len: u64) -> Result<Option<Vec<u8>>> {
         match &self {
-            Body::Bytes(_) => Ok(None),
-            Body::Stream(s) => s.input_stream.chunk(len),
+            Body::Bytes(_) | Body::Reader(_) => Ok(None),
+            Body::Stream(s) => s.chunk(len),
         }
     }
 
-    pub fn bytes(self) -> Result<Vec<u8>> {
+    /// Put back data read from the body, so that it is read again before the rest.
+    ///
+    /// Unlike chaining readers, an incoming body keeps its trailers.
+    pub(crate) fn unread(&mut self, mut data: Vec<u8>) {
         match self {
-            Body::Bytes(data) => Ok(data),
+            Body::Stream(s) => {
+                let buffer = s.buffer.get_mut();
+                data.extend_from_slice(&buffer.data[buffer.pos..]);
+                *buffer = Buffer { data, pos: 0 };
+            }
+            body => {
+                let rest = std::mem::replace(body, Body::from(vec![]));
+                *body = Body::reader(Cursor::new(data).chain(rest));
+            }
+        }
+    }
+
+    /// Finish the body and wait for the trailers, any unread data is discarded.
+    ///
+    /// It always returns None for outgoing bodies.
+    pub fn finish(&mut self) -> Result<Option<HeaderMap>> {
+        match std::mem::replace(self, Body::from(vec![])) {
+            Body::Stream(s) => s.finish(),
+            body => {
+                *self = body;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Read the whole body, failing with [`BodyTooLarge`] as soon as more than `limit` bytes are read.
+    pub fn bytes_limited(self, limit: u64) -> Result<Vec<u8>> {
+        match self {
+            Body::Bytes(cursor) => {
+                // skip the data that has already been read
+                let pos = cursor.position() as usize;
+                let mut data = cursor.into_inner();
+                data.drain(..pos);
+                if data.len() as u64 > limit {
+                    return Err(BodyTooLarge::new(limit).into());
+                }
+                Ok(data)
+            }
             Body::Stream(s) => {
                 let mut body = Vec::new();
-                while let Some(mut chunk) = s.input_stream.chunk(1024 * 1024)? {
+                while let Some(mut chunk) = s.chunk(CHUNK_SIZE)? {
+                    if (body.len() + chunk.len()) as u64 > limit {
+                        return Err(BodyTooLarge::new(limit).into());
+                    }
                     body.append(&mut chunk);
                 }
                 Ok(body)
             }
+            Body::Reader(reader) => {
+                let mut body = Vec::new();
+                reader
+                    .take(limit.saturating_add(1))
+                    .read_to_end(&mut body)?;
+                if body.len() as u64 > limit {
+                    return Err(BodyTooLarge::new(limit).into());
+                }
+                Ok(body)
+            }
         }
     }
 }
 
-pub(crate) fn write_to_outgoing_body(outgoing_body: &OutgoingBody, mut buf: &[u8]) -> Result<()> {
-    if buf.is_empty() {
-        return Ok(());
+impl Read for Body {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Body::Bytes(cursor) => cursor.read(buf),
+            Body::Stream(s) => s.read(buf),
+            Body::Reader(reader) => reader.read(buf),
+        }
+    }
+}
+
+impl BufRead for Body {
+    #[inline]
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match self {
+            Body::Bytes(cursor) => cursor.fill_buf(),
+            Body::Stream(s) => s.fill_buf(),
+            Body::Reader(reader) => reader.fill_buf(),
+        }
     }
 
+    #[inline]
+    fn consume(&mut self, amt: usize) {
+        match self {
+            Body::Bytes(cursor) => cursor.consume(amt),
+            Body::Stream(s) => s.consume(amt),
+            Body::Reader(reader) => reader.consume(amt),
+        }
+    }
+}
+
+pub(crate) fn write_to_outgoing_body(outgoing_body: &OutgoingBody, mut body: Body) -> Result<()> {
     let out = outgoing_body
         .write()
         .map_err(|_| anyhow!("outgoing request write failed"))?;
 
+    loop {
+        let buf = body.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(());
+        }
+        write_all(&out, buf)?;
+        let len = buf.len();
+        body.consume(len);
+    }
+}
+
+fn write_all(out: &OutputStream, mut buf: &[u8]) -> Result<()> {
     let pollable = out.subscribe();
     while !buf.is_empty() {
         pollable.block();
@@ -0,0 +1,933 @@
+//! A private HTTP cache for [`Client`](crate::Client), following RFC 9111.
+//!
+//! ```
+//! # use anyhow::Result;
+//! # use waki::{cache::Cache, Client};
+//! # fn run() -> Result<()> {
+//! let client = Client::new().cache(Cache::default());
+//! // later requests are answered from the cache while the response is fresh
+//! let resp = client.get("https://example.com/.well-known/jwks.json").send()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! GET responses are stored according to their `Cache-Control` and `Expires` headers and the
+//! `Vary` header is respected. Stale responses are revalidated with their `ETag` and
+//! `Last-Modified` headers, and successful unsafe requests invalidate the stored response of
+//! their target URI.
+//!
+//! Entries are kept by a [`CacheStore`], [`MemoryStore`] by default.
+
+use crate::{
+    body::Body,
+    header::{
+        HeaderMap, HeaderName, HeaderValue, AGE, CACHE_CONTROL, CONTENT_LENGTH, ETAG, EXPIRES,
+        IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, IF_UNMODIFIED_SINCE, LAST_MODIFIED,
+        RANGE, VARY,
+    },
+    headers::{Age, CacheControl, Date, Expires, Header, LastModified, Vary},
+    Method, Request, Response,
+};
+
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// status codes that are cacheable by default, RFC 9110 section 15.1
+const HEURISTICALLY_CACHEABLE: [u16; 11] = [200, 203, 204, 300, 301, 308, 404, 405, 410, 414, 501];
+// the heuristic freshness lifetime is capped, as suggested by RFC 9111 section 4.2.2
+const MAX_HEURISTIC_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The storage of cached responses, keyed by URI.
+///
+/// Implement it to persist the cache elsewhere, e.g. with wasi-keyvalue, entries can be
+/// serialized with [`CacheEntry::to_bytes`].
+pub trait CacheStore: Send + Sync {
+    fn get(&self, key: &str) -> Option<CacheEntry>;
+
+    fn put(&self, key: &str, entry: CacheEntry);
+
+    fn remove(&self, key: &str);
+}
+
+/// Keeps the cached responses in memory.
+///
+/// Once the stored responses exceed the maximum size, 16 MiB by default, the ones that were
+/// received first are evicted.
+#[derive(Debug)]
+pub struct MemoryStore {
+    entries: Mutex<HashMap<String, CacheEntry>>,
+    max_size: usize,
+}
+
+impl Default for MemoryStore {
+    #[inline]
+    fn default() -> Self {
+        Self::with_max_size(16 * 1024 * 1024)
+    }
+}
+
+impl MemoryStore {
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Create a store keeping at most `max_size` bytes of responses, headers included.
+    pub fn with_max_size(max_size: usize) -> Self {
+        Self {
+            entries: Default::default(),
+            max_size,
+        }
+    }
+}
+
+impl CacheStore for MemoryStore {
+    fn get(&self, key: &str) -> Option<CacheEntry> {
+        self.entries.lock().unwrap().get(key).cloned()
+    }
+
+    fn put(&self, key: &str, entry: CacheEntry) {
+        let mut entries = self.entries.lock().unwrap();
+        if entry.size() > self.max_size {
+            // the previous response is outdated
+            entries.remove(key);
+            return;
+        }
+        entries.insert(key.to_string(), entry);
+        let mut size = entries.values().map(CacheEntry::size).sum::<usize>();
+        while size > self.max_size {
+            let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.response_time)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            if let Some(entry) = entries.remove(&oldest) {
+                size -= entry.size();
+            }
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        self.entries.lock().unwrap().remove(key);
+    }
+}
+
+/// Keeps the cached responses as files in a directory, e.g. a WASI preopened directory.
+#[cfg(feature = "fs")]
+#[derive(Debug)]
+pub struct FileStore {
+    dir: std::path::PathBuf,
+}
+
+#[cfg(feature = "fs")]
+impl FileStore {
+    /// Use the directory, which must exist.
+    pub fn new<P: Into<std::path::PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, key: &str) -> std::path::PathBuf {
+        // the tag is made of the length and hash of the key, so it is a valid file name
+        let tag = crate::headers::EntityTag::from_content(key.as_bytes());
+        self.dir.join(tag.tag())
+    }
+}
+
+#[cfg(feature = "fs")]
+impl CacheStore for FileStore {
+    fn get(&self, key: &str) -> Option<CacheEntry> {
+        let data = std::fs::read(self.path(key)).ok()?;
+        // the key is stored first, to detect hash collisions
+        let rest = data
+            .strip_prefix(key.as_bytes())
+            .and_then(|rest| rest.strip_prefix(b"\n"))?;
+        CacheEntry::from_bytes(rest).ok()
+    }
+
+    fn put(&self, key: &str, entry: CacheEntry) {
+        let mut data = format!("{}\n", key).into_bytes();
+        data.append(&mut entry.to_bytes());
+        // the cache is an optimization, failing to write is not an error
+        let _ = std::fs::write(self.path(key), data);
+    }
+
+    fn remove(&self, key: &str) {
+        let _ = std::fs::remove_file(self.path(key));
+    }
+}
+
+/// A stored response.
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    status_code: u16,
+    headers: HeaderMap,
+    body: Vec<u8>,
+    // the request headers selected by the Vary header of the response
+    vary: HeaderMap,
+    request_time: SystemTime,
+    response_time: SystemTime,
+}
+
+impl CacheEntry {
+    #[inline]
+    pub fn status_code(&self) -> u16 {
+        self.status_code
+    }
+
+    #[inline]
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    #[inline]
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Get the time at which the response was received.
+    #[inline]
+    pub fn response_time(&self) -> SystemTime {
+        self.response_time
+    }
+
+    // the approximate memory used by the entry
+    fn size(&self) -> usize {
+        let headers = self.headers.iter().chain(self.vary.iter());
+        let headers = headers.map(|(name, value)| name.as_str().len() + value.len());
+        self.body.len() + headers.sum::<usize>()
+    }
+
+    /// Serialize the entry, to be read back with [`CacheEntry::from_bytes`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = self.status_code.to_be_bytes().to_vec();
+        for time in [self.request_time, self.response_time] {
+            let time = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+            data.extend(time.as_secs().to_be_bytes());
+            data.extend(time.subsec_nanos().to_be_bytes());
+        }
+        for headers in [&self.headers, &self.vary] {
+            data.extend((headers.len() as u32).to_be_bytes());
+            for (name, value) in headers {
+                for bytes in [name.as_str().as_bytes(), value.as_bytes()] {
+                    data.extend((bytes.len() as u32).to_be_bytes());
+                    data.extend(bytes);
+                }
+            }
+        }
+        data.extend(&self.body);
+        data
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut data = Cursor::new(data);
+        let status_code = u16::from_be_bytes(take(&mut data)?);
+        let mut times = [UNIX_EPOCH; 2];
+        for time in &mut times {
+            let secs = u64::from_be_bytes(take(&mut data)?);
+            let nanos = u32::from_be_bytes(take(&mut data)?);
+            *time = (nanos < 1_000_000_000)
+                .then(|| UNIX_EPOCH.checked_add(Duration::new(secs, nanos)))
+                .flatten()
+                .ok_or_else(|| anyhow!("invalid cache entry"))?;
+        }
+        let mut maps = [HeaderMap::new(), HeaderMap::new()];
+        for headers in &mut maps {
+            for _ in 0..u32::from_be_bytes(take(&mut data)?) {
+                let name = HeaderName::from_bytes(&take_vec(&mut data)?)?;
+                let value = HeaderValue::from_bytes(&take_vec(&mut data)?)?;
+                headers.append(name, value);
+            }
+        }
+        let mut body = vec![];
+        data.read_to_end(&mut body)?;
+        let [headers, vary] = maps;
+        let [request_time, response_time] = times;
+        Ok(Self {
+            status_code,
+            headers,
+            body,
+            vary,
+            request_time,
+            response_time,
+        })
+    }
+
+    /// Check the request headers selected by the Vary header.
+    fn matches(&self, headers: &HeaderMap) -> bool {
+        match typed::<Vary>(&self.headers) {
+            Some(Vary::Headers(names)) => names
+                .iter()
+                .all(|name| headers.get_all(name).iter().eq(self.vary.get_all(name))),
+            Some(Vary::Any) => false,
+            None => !self.headers.contains_key(VARY),
+        }
+    }
+
+    fn freshness_lifetime(&self) -> Duration {
+        if let Some(max_age) = cache_control(&self.headers).max_age {
+            return max_age;
+        }
+        let date = match typed::<Date>(&self.headers) {
+            Some(Date(date)) => date,
+            None => self.response_time,
+        };
+        if self.headers.contains_key(EXPIRES) {
+            // invalid dates, such as `0`, represent a time in the past
+            return match typed::<Expires>(&self.headers) {
+                Some(Expires(expires)) => expires.duration_since(date).unwrap_or_default(),
+                None => Duration::ZERO,
+            };
+        }
+        match typed::<LastModified>(&self.headers) {
+            Some(LastModified(last_modified))
+                if HEURISTICALLY_CACHEABLE.contains(&self.status_code) =>
+            {
+                (date.duration_since(last_modified).unwrap_or_default() / 10)
+                    .min(MAX_HEURISTIC_LIFETIME)
+            }
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Compute the current age, RFC 9111 section 4.2.3.
+    fn age(&self, now: SystemTime) -> Duration {
+        let since = |later: SystemTime, earlier: SystemTime| {
+            later.duration_since(earlier).unwrap_or_default()
+        };
+        let apparent_age = match typed::<Date>(&self.headers) {
+            Some(Date(date)) => since(self.response_time, date),
+            None => Duration::ZERO,
+        };
+        let age_value = typed::<Age>(&self.headers)
+            .map(|age| age.0)
+            .unwrap_or_default();
+        let response_delay = since(self.response_time, self.request_time);
+        let corrected_initial_age = apparent_age.max(age_value.saturating_add(response_delay));
+        corrected_initial_age.saturating_add(since(now, self.response_time))
+    }
+
+    /// Check if the entry can be used without revalidation.
+    fn is_usable(&self, request: &CacheControl, now: SystemTime) -> bool {
+        let response = cache_control(&self.headers);
+        if request.no_cache || response.no_cache {
+            return false;
+        }
+        let age = self.age(now);
+        if request.max_age.is_some_and(|max_age| age > max_age) {
+            return false;
+        }
+        let lifetime = self.freshness_lifetime();
+        let min_fresh = request.min_fresh.unwrap_or_default();
+        if age.saturating_add(min_fresh) < lifetime {
+            return true;
+        }
+        // serving stale responses
+        !response.must_revalidate
+            && request
+                .max_stale
+                .is_some_and(|max_stale| age.saturating_sub(lifetime) <= max_stale)
+    }
+
+    /// Update the stored headers with the ones of a `304 Not Modified` response.
+    fn update(&mut self, headers: &HeaderMap, request_time: SystemTime, response_time: SystemTime) {
+        for name in headers.keys() {
+            if name != CONTENT_LENGTH {
+                self.headers.remove(name);
+            }
+        }
+        for (name, value) in headers {
+            if name != CONTENT_LENGTH {
+                self.headers.append(name, value.clone());
+            }
+        }
+        self.request_time = request_time;
+        self.response_time = response_time;
+    }
+
+    fn to_response(&self, now: SystemTime) -> Response {
+        let mut resp = status(self.status_code);
+        resp.headers = self.headers.clone();
+        resp.headers.insert(AGE, self.age(now).as_secs().into());
+        resp.body = Body::from(self.body.clone());
+        resp
+    }
+}
+
+fn take<const N: usize>(data: &mut Cursor<&[u8]>) -> Result<[u8; N]> {
+    let mut buf = [0; N];
+    data.read_exact(&mut buf)
+        .map_err(|_| anyhow!("invalid cache entry"))?;
+    Ok(buf)
+}
+
+fn take_vec(data: &mut Cursor<&[u8]>) -> Result<Vec<u8>> {
+    let len = u32::from_be_bytes(take(data)?) as usize;
+    let remaining = data.get_ref().len() - data.position() as usize;
+    if len > remaining {
+        return Err(anyhow!("invalid cache entry"));
+    }
+    let mut buf = vec![0; len];
+    data.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn typed<H: Header>(headers: &HeaderMap) -> Option<H> {
+    let name = H::name();
+    if !headers.contains_key(&name) {
+        return None;
+    }
+    H::decode(headers.get_all(name).iter()).ok()
+}
+
+fn cache_control(headers: &HeaderMap) -> CacheControl {
+    match typed::<CacheControl>(headers) {
+        Some(cc) => cc,
+        // an invalid header is not understood, so nothing is cached
+        None if headers.contains_key(CACHE_CONTROL) => CacheControl::new().no_store(),
+        None => CacheControl::new(),
+    }
+}
+
+fn status(status_code: u16) -> Response {
+    Response::builder()
+        .status_code(status_code)
+        .build()
+        .unwrap()
+}
+
+/// A cache used by a [`Client`](crate::Client).
+pub struct Cache {
+    store: Box<dyn CacheStore>,
+    max_entry_size: u64,
+}
+
+impl Default for Cache {
+    #[inline]
+    fn default() -> Self {
+        Self::new(MemoryStore::new())
+    }
+}
+
+impl Cache {
+    /// Create a cache with the store, responses larger than 1 MiB are not stored.
+    pub fn new<S: CacheStore + 'static>(store: S) -> Self {
+        Self {
+            store: Box::new(store),
+            max_entry_size: 1024 * 1024,
+        }
+    }
+
+    /// Set the maximum size of the body of the stored responses.
+    pub fn max_entry_size(mut self, size: u64) -> Self {
+        self.max_entry_size = size;
+        self
+    }
+
+    pub(crate) fn send<F>(&self, req: Request, fetch: F) -> Result<Response>
+    where
+        F: FnOnce(Request) -> Result<Response>,
+    {
+        self.send_with(req, SystemTime::now, fetch)
+    }
+
+    fn send_with<C, F>(&self, mut req: Request, now: C, fetch: F) -> Result<Response>
+    where
+        C: Fn() -> SystemTime,
+        F: FnOnce(Request) -> Result<Response>,
+    {
+        let key = key(&req);
+        match req.method() {
+            Method::Get => {}
+            Method::Head | Method::Options | Method::Trace => return fetch(req),
+            _ => {
+                let resp = fetch(req)?;
+                if (200..400).contains(&resp.status_code()) {
+                    self.store.remove(&key);
+                }
+                return Ok(resp);
+            }
+        }
+        let request = cache_control(&req.headers);
+        // conditional and range requests are sent as is
+        let conditional = [
+            IF_MATCH,
+            IF_NONE_MATCH,
+            IF_MODIFIED_SINCE,
+            IF_UNMODIFIED_SINCE,
+            IF_RANGE,
+            RANGE,
+        ]
+        .iter()
+        .any(|name| req.headers.contains_key(name));
+        if request.no_store || conditional {
+            return fetch(req);
+        }
+
+        let entry = self
+            .store
+            .get(&key)
+            .filter(|entry| entry.matches(&req.headers));
+        if let Some(entry) = &entry {
+            let now = now();
+            if entry.is_usable(&request, now) {
+                return Ok(entry.to_response(now));
+            }
+        }
+        if request.only_if_cached {
+            return Ok(status(504));
+        }
+
+        let headers = req.headers.clone();
+        if let Some(entry) = &entry {
+            if let Some(etag) = entry.headers.get(ETAG) {
+                req.headers.insert(IF_NONE_MATCH, etag.clone());
+            }
+            if let Some(last_modified) = entry.headers.get(LAST_MODIFIED) {
+                req.headers.insert(IF_MODIFIED_SINCE, last_modified.clone());
+            }
+        }
+        let request_time = now();
+        let mut resp = fetch(req)?;
+        let response_time = now();
+
+        if let Some(mut entry) = entry.filter(|_| resp.status_code() == 304) {
+            entry.update(&resp.headers, request_time, response_time);
+            self.store.put(&key, entry.clone());
+            return Ok(entry.to_response(response_time));
+        }
+        if !storable(&resp) {
+            return Ok(resp);
+        }
+
+        let mut data = vec![];
+        (&mut resp.body)
+            .take(self.max_entry_size.saturating_add(1))
+            .read_to_end(&mut data)?;
+        if data.len() as u64 > self.max_entry_size {
+            // the response is passed through with its trailers
+            resp.body.unread(data);
+            return Ok(resp);
+        }
+        if let Some(trailers) = resp.body.finish()? {
+            resp.trailers = Some(trailers);
+        }
+
+        let mut vary = HeaderMap::new();
+        if let Some(Vary::Headers(names)) = typed::<Vary>(&resp.headers) {
+            for name in names {
+                for value in headers.get_all(&name) {
+                    vary.append(name.clone(), value.clone());
+                }
+            }
+        }
+        let entry = CacheEntry {
+            status_code: resp.status_code(),
+            headers: resp.headers.clone(),
+            body: data.clone(),
+            vary,
+            request_time,
+            response_time,
+        };
+        if entry.freshness_lifetime() > Duration::ZERO
+            || entry.headers.contains_key(ETAG)
+            || entry.headers.contains_key(LAST_MODIFIED)
+        {
+            self.store.put(&key, entry);
+        }
+        resp.body = Body::from(data);
+        Ok(resp)
+    }
+}
+
+fn key(req: &Request) -> String {
+    let uri = &req.uri;
+    let mut key = String::new();
+    if let Some(scheme) = &uri.scheme {
+        key += scheme.as_str();
+        key += "://";
+    }
+    if let Some(authority) = &uri.authority {
+        key += authority.as_str();
+    }
+    if let Some(path_and_query) = &uri.path_and_query {
+        key += path_and_query.as_str();
+    }
+    key
+}
+
+/// Check if the response can be stored, RFC 9111 section 3.
+fn storable(resp: &Response) -> bool {
+    let status_code = resp.status_code();
+    let cc = cache_control(&resp.headers);
+    if cc.no_store || matches!(typed::<Vary>(&resp.headers), Some(Vary::Any)) {
+        return false;
+    }
+    if resp.headers.contains_key(VARY) && typed::<Vary>(&resp.headers).is_none() {
+        return false;
+    }
+    let explicit = cc.max_age.is_some() || resp.headers.contains_key(EXPIRES);
+    HEURISTICALLY_CACHEABLE.contains(&status_code)
+        || (explicit
+            && (200..600).contains(&status_code)
+            && status_code != 206
+            && status_code != 304)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    const START: u64 = 1_700_000_000;
+
+    struct Server {
+        now: Cell<u64>,
+        requests: RefCell<Vec<Request>>,
+    }
+
+    impl Server {
+        fn new() -> Self {
+            Self {
+                now: Cell::new(START),
+                requests: RefCell::new(vec![]),
+            }
+        }
+
+        fn now(&self) -> SystemTime {
+            UNIX_EPOCH + Duration::from_secs(self.now.get())
+        }
+
+        fn advance(&self, secs: u64) {
+            self.now.set(self.now.get() + secs);
+        }
+
+        fn requests(&self) -> usize {
+            self.requests.borrow().len()
+        }
+
+        /// Send the request, answering with the response if it is not answered by the cache.
+        fn send(
+            &self,
+            cache: &Cache,
+            req: Target,
+            status_code: u16,
+            headers: &[(&'static str, &str)],
+            body: &str,
+        ) -> Response {
+            let mut request = Request::new(req.0, req.1.parse::<http::Uri>().unwrap().into_parts());
+            for (k, v) in req.2 {
+                request
+                    .headers
+                    .append(*k, HeaderValue::from_str(v).unwrap());
+            }
+            cache
+                .send_with(
+                    request,
+                    || self.now(),
+                    |req| {
+                        self.requests.borrow_mut().push(req);
+                        let mut resp = status(status_code);
+                        resp.headers.insert(
+                            crate::header::DATE,
+                            httpdate::fmt_http_date(self.now()).parse()?,
+                        );
+                        for (k, v) in headers {
+                            resp.headers.append(*k, HeaderValue::from_str(v)?);
+                        }
+                        resp.body = Body::from(body.as_bytes().to_vec());
+                        Ok(resp)
+                    },
+                )
+                .unwrap()
+        }
+
+        fn get(&self, cache: &Cache, headers: &[(&'static str, &str)]) -> String {
+            let resp = self.send(
+                cache,
+                (Method::Get, "https://example.com/a", &[]),
+                200,
+                headers,
+                "fresh",
+            );
+            String::from_utf8(resp.body().unwrap()).unwrap()
+        }
+    }
+
+    type Target<'a> = (Method, &'a str, &'a [(&'static str, &'a str)]);
+
+    #[test]
+    fn test_max_age() {
+        let server = Server::new();
+        let cache = Cache::default();
+        let headers = &[("cache-control", "max-age=60")];
+        assert_eq!(server.get(&cache, headers), "fresh");
+        server.advance(30);
+        let resp = server.send(
+            &cache,
+            (Method::Get, "https://example.com/a", &[]),
+            500,
+            &[],
+            "",
+        );
+        assert_eq!(resp.status_code(), 200);
+        assert_eq!(resp.header(AGE).unwrap(), "30");
+        assert_eq!(resp.body().unwrap(), b"fresh");
+        assert_eq!(server.requests(), 1);
+
+        // another URI
+        server.send(
+            &cache,
+            (Method::Get, "https://example.com/b", &[]),
+            200,
+            headers,
+            "",
+        );
+        assert_eq!(server.requests(), 2);
+
+        server.advance(30);
+        server.get(&cache, headers);
+        assert_eq!(server.requests(), 3);
+    }
+
+    #[test]
+    fn test_not_stored() {
+        let server = Server::new();
+        let cache = Cache::default();
+        for headers in [
+            &[("cache-control", "no-store, max-age=60")][..],
+            &[("cache-control", "max-age=60"), ("vary", "*")],
+            &[("cache-control", "max-age=invalid")],
+            // neither freshness nor validators
+            &[],
+        ] {
+            server.get(&cache, headers);
+            server.get(&cache, headers);
+        }
+        assert_eq!(server.requests(), 8);
+
+        let cache = Cache::default().max_entry_size(2);
+        let headers = &[("cache-control", "max-age=60")];
+        assert_eq!(server.get(&cache, headers), "fresh");
+        assert_eq!(server.get(&cache, headers), "fresh");
+        assert_eq!(server.requests(), 10);
+    }
+
+    #[test]
+    fn test_expires() {
+        let server = Server::new();
+        let cache = Cache::default();
+        let expires = httpdate::fmt_http_date(server.now() + Duration::from_secs(10));
+        let headers = &[("expires", expires.as_str())];
+        server.get(&cache, headers);
+        server.advance(9);
+        server.get(&cache, headers);
+        assert_eq!(server.requests(), 1);
+        server.advance(1);
+        server.get(&cache, headers);
+        assert_eq!(server.requests(), 2);
+
+        // invalid dates are in the past
+        let cache = Cache::default();
+        let headers = &[("expires", "0"), ("etag", "\"a\"")];
+        server.get(&cache, headers);
+        server.get(&cache, headers);
+        assert_eq!(server.requests(), 4);
+    }
+
+    #[test]
+    fn test_heuristic_freshness() {
+        let server = Server::new();
+        let cache = Cache::default();
+        let last_modified = httpdate::fmt_http_date(server.now() - Duration::from_secs(1000));
+        let headers = &[("last-modified", last_modified.as_str())];
+        server.get(&cache, headers);
+        server.advance(99);
+        server.get(&cache, headers);
+        assert_eq!(server.requests(), 1);
+        server.advance(1);
+        server.get(&cache, headers);
+        assert_eq!(server.requests(), 2);
+    }
+
+    #[test]
+    fn test_revalidation() {
+        let server = Server::new();
+        let cache = Cache::default();
+        server.get(&cache, &[("cache-control", "no-cache"), ("etag", "\"v1\"")]);
+
+        let resp = server.send(
+            &cache,
+            (Method::Get, "https://example.com/a", &[]),
+            304,
+            &[("cache-control", "max-age=60"), ("etag", "\"v1\"")],
+            "",
+        );
+        assert_eq!(resp.status_code(), 200);
+        assert_eq!(resp.header(CACHE_CONTROL).unwrap(), "max-age=60");
+        assert_eq!(resp.body().unwrap(), b"fresh");
+        {
+            let requests = server.requests.borrow();
+            assert_eq!(requests[1].headers.get(IF_NONE_MATCH).unwrap(), "\"v1\"");
+        }
+
+        // the updated headers make the response fresh
+        server.advance(10);
+        assert_eq!(server.get(&cache, &[]), "fresh");
+        assert_eq!(server.requests(), 2);
+
+        // a full response replaces the stored one
+        server.advance(60);
+        let resp = server.send(
+            &cache,
+            (Method::Get, "https://example.com/a", &[]),
+            200,
+            &[("cache-control", "max-age=60")],
+            "new",
+        );
+        assert_eq!(resp.body().unwrap(), b"new");
+        assert_eq!(server.get(&cache, &[]), "new");
+        assert_eq!(server.requests(), 3);
+    }
+
+    #[test]
+    fn test_request_directives() {
+        let server = Server::new();
+        let cache = Cache::default();
+        let headers = &[("cache-control", "max-age=60")];
+        server.get(&cache, headers);
+        server.advance(70);
+
+        let send = |cc| {
+            server.send(
+                &cache,
+                (
+                    Method::Get,
+                    "https://example.com/a",
+                    &[("cache-control", cc)],
+                ),
+                200,
+                &[],
+                "",
+            )
+        };
+        assert_eq!(send("max-stale=20").body().unwrap(), b"fresh");
+        assert_eq!(send("max-stale").body().unwrap(), b"fresh");
+        assert_eq!(server.requests(), 1);
+        assert_eq!(send("only-if-cached").status_code(), 504);
+        assert_eq!(server.requests(), 1);
+        // the response without freshness is not stored, so the entry is kept
+        send("max-stale=5");
+        send("no-store");
+        assert_eq!(server.requests(), 3);
+        assert_eq!(send("max-stale=20").body().unwrap(), b"fresh");
+        assert_eq!(send("max-stale=20, max-age=60").body().unwrap(), b"");
+        assert_eq!(server.requests(), 4);
+    }
+
+    #[test]
+    fn test_vary() {
+        let server = Server::new();
+        let cache = Cache::default();
+        let send = |lang| {
+            server.send(
+                &cache,
+                (
+                    Method::Get,
+                    "https://example.com/a",
+                    &[("accept-language", lang)],
+                ),
+                200,
+                &[("cache-control", "max-age=60"), ("vary", "Accept-Language")],
+                lang,
+            )
+        };
+        assert_eq!(send("en").body().unwrap(), b"en");
+        assert_eq!(send("en").body().unwrap(), b"en");
+        assert_eq!(server.requests(), 1);
+        assert_eq!(send("fr").body().unwrap(), b"fr");
+        assert_eq!(server.requests(), 2);
+    }
+
+    #[test]
+    fn test_invalidation() {
+        let server = Server::new();
+        let cache = Cache::default();
+        let headers = &[("cache-control", "max-age=60")];
+        server.get(&cache, headers);
+        server.send(
+            &cache,
+            (Method::Post, "https://example.com/a", &[]),
+            500,
+            &[],
+            "",
+        );
+        server.get(&cache, headers);
+        assert_eq!(server.requests(), 2);
+        server.send(
+            &cache,
+            (Method::Delete, "https://example.com/a", &[]),
+            204,
+            &[],
+            "",
+        );
+        server.get(&cache, headers);
+        assert_eq!(server.requests(), 4);
+    }
+
+    #[test]
+    fn test_memory_store() {
+        let entry = |time: u64, body: &str| CacheEntry {
+            status_code: 200,
+            headers: HeaderMap::new(),
+            body: body.as_bytes().to_vec(),
+            vary: HeaderMap::new(),
+            request_time: UNIX_EPOCH + Duration::from_secs(time),
+            response_time: UNIX_EPOCH + Duration::from_secs(time),
+        };
+        let store = MemoryStore::with_max_size(10);
+        store.put("b", entry(START + 1, "1234"));
+        store.put("a", entry(START, "1234"));
+        assert!(store.get("a").is_some());
+        // the oldest response is evicted
+        store.put("c", entry(START + 2, "1234"));
+        assert!(store.get("a").is_none());
+        assert!(store.get("b").is_some());
+        assert!(store.get("c").is_some());
+        // a response larger than the store is not kept, and does not evict the others
+        store.put("c", entry(START + 3, "12345678901"));
+        assert!(store.get("c").is_none());
+        assert!(store.get("b").is_some());
+    }
+
+    #[test]
+    fn test_entry_bytes() {
+        let mut headers = HeaderMap::new();
+        headers.append("set-cookie", HeaderValue::from_static("a=1"));
+        headers.append("set-cookie", HeaderValue::from_static("b=2"));
+        let mut vary = HeaderMap::new();
+        vary.insert("accept", HeaderValue::from_static("*/*"));
+        let entry = CacheEntry {
+            status_code: 404,
+            headers,
+            body: b"not found".to_vec(),
+            vary,
+            request_time: UNIX_EPOCH + Duration::new(START, 5),
+            response_time: UNIX_EPOCH + Duration::from_secs(START + 1),
+        };
+        let decoded = CacheEntry::from_bytes(&entry.to_bytes()).unwrap();
+        assert_eq!(decoded.status_code(), 404);
+        assert_eq!(decoded.headers(), entry.headers());
+        assert_eq!(decoded.vary, entry.vary);
+        assert_eq!(decoded.body(), b"not found");
+        assert_eq!(decoded.request_time, entry.request_time);
+        assert_eq!(decoded.response_time(), entry.response_time());
+
+        let bytes = entry.to_bytes();
+        assert!(CacheEntry::from_bytes(&bytes[..20]).is_err());
+    }
+}
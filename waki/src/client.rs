@@ -1,7 +1,12 @@
-use crate::{Method, RequestBuilder};
+use crate::{cache::Cache, Method, Proxy, RequestBuilder};
 
-#[derive(Default)]
-pub struct Client {}
+use std::sync::Arc;
+
+#[derive(Default, Clone)]
+pub struct Client {
+    cache: Option<Arc<Cache>>,
+    proxies: Arc<[Proxy]>,
+}
 
 impl Client {
     #[inline]
@@ -41,6 +46,37 @@ impl Client {
 
     #[inline]
     pub fn request(&self, method: Method, url: &str) -> RequestBuilder {
-        RequestBuilder::new(method, url)
+        let mut builder = RequestBuilder::new(method, url);
+        builder.cache = self.cache.clone();
+        builder.proxies = self.proxies.clone();
+        builder
+    }
+
+    /// Send the requests through the proxy, if several proxies are set the first one
+    /// intercepting the request is used.
+    ///
+    /// ```
+    /// # use waki::{Client, Proxy};
+    /// # fn run() {
+    /// let client = Client::new().proxy(Proxy::from_env());
+    /// # }
+    /// ```
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        let mut proxies = self.proxies.to_vec();
+        proxies.push(proxy);
+        self.proxies = proxies.into();
+        self
+    }
+
+    /// Answer GET requests from the cache when possible, see [`cache`](crate::cache).
+    ///
+    /// ```
+    /// # use waki::{cache::Cache, Client};
+    /// let client = Client::new().cache(Cache::default().max_entry_size(64 * 1024));
+    /// ```
+    #[inline]
+    pub fn cache(mut self, cache: Cache) -> Self {
+        self.cache = Some(Arc::new(cache));
+        self
     }
 }
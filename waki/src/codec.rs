@@ -0,0 +1,224 @@
+//! Body formats used by [`RequestBuilder::encode`](crate::RequestBuilder::encode) and
+//! [`Request::decode`](crate::Request::decode).
+//!
+//! The built-in codecs are enabled by the features of the same name, custom formats can be
+//! supported by implementing [`Codec`]:
+//!
+//! ```
+//! # use anyhow::Result;
+//! # use serde::{de::DeserializeOwned, Serialize};
+//! # use waki::codec::Codec;
+//! # #[cfg(feature = "json")]
+//! /// JSON, indented for readability.
+//! struct PrettyJson;
+//!
+//! # #[cfg(feature = "json")]
+//! impl Codec for PrettyJson {
+//!     fn content_type(&self) -> &str {
+//!         "application/json"
+//!     }
+//!
+//!     fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>> {
+//!         Ok(serde_json::to_vec_pretty(value)?)
+//!     }
+//!
+//!     fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T> {
+//!         Ok(serde_json::from_slice(data)?)
+//!     }
+//! }
+//!
+//! # #[cfg(feature = "json")]
+//! # fn main() -> Result<()> {
+//! let data = PrettyJson.encode(&[1, 2])?;
+//! assert_eq!(data, b"[\n  1,\n  2\n]");
+//! let value: Vec<u32> = PrettyJson.decode(&data)?;
+//! assert_eq!(value, [1, 2]);
+//! # Ok(())
+//! # }
+//! # #[cfg(not(feature = "json"))]
+//! # fn main() {}
+//! ```
+
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// A format that values can be encoded into and decoded from.
+pub trait Codec {
+    /// Get the MIME type of the format, which is set as the Content-Type of encoded bodies.
+    fn content_type(&self) -> &str;
+
+    /// Encode the value.
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>>;
+
+    /// Decode a value from the data.
+    fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T>;
+}
+
+/// JSON, `application/json`.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json;
+
+#[cfg(feature = "json")]
+impl Codec for Json {
+    fn content_type(&self) -> &str {
+        "application/json"
+    }
+
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(value)?)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T> {
+        Ok(serde_json::from_slice(data)?)
+    }
+}
+
+/// CBOR, `application/cbor`.
+#[cfg(feature = "cbor")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cbor;
+
+#[cfg(feature = "cbor")]
+impl Codec for Cbor {
+    fn content_type(&self) -> &str {
+        "application/cbor"
+    }
+
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>> {
+        let mut data = vec![];
+        ciborium::into_writer(value, &mut data)?;
+        Ok(data)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T> {
+        Ok(ciborium::from_reader(data)?)
+    }
+}
+
+/// MessagePack, `application/msgpack`.
+///
+/// Structs are encoded as maps with field names, rather than arrays.
+#[cfg(feature = "msgpack")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MsgPack;
+
+#[cfg(feature = "msgpack")]
+impl Codec for MsgPack {
+    fn content_type(&self) -> &str {
+        "application/msgpack"
+    }
+
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>> {
+        Ok(rmp_serde::to_vec_named(value)?)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T> {
+        Ok(rmp_serde::from_slice(data)?)
+    }
+}
+
+/// XML, `application/xml`.
+///
+/// The root element is named after the serialized type.
+#[cfg(feature = "xml")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Xml;
+
+#[cfg(feature = "xml")]
+impl Codec for Xml {
+    fn content_type(&self) -> &str {
+        "application/xml"
+    }
+
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>> {
+        Ok(quick_xml::se::to_string(value)?.into_bytes())
+    }
+
+    fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T> {
+        Ok(quick_xml::de::from_str(std::str::from_utf8(data)?)?)
+    }
+}
+
+/// TOML, `application/toml`.
+#[cfg(feature = "toml")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Toml;
+
+#[cfg(feature = "toml")]
+impl Codec for Toml {
+    fn content_type(&self) -> &str {
+        "application/toml"
+    }
+
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>> {
+        Ok(toml::to_string(value)?.into_bytes())
+    }
+
+    fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T> {
+        Ok(toml::from_str(std::str::from_utf8(data)?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Data {
+        name: String,
+        count: u32,
+        tags: Vec<String>,
+    }
+
+    #[allow(dead_code)]
+    fn roundtrip<C: Codec>(codec: C) -> Result<()> {
+        let data = Data {
+            name: "waki".into(),
+            count: 42,
+            tags: vec!["wasi".into(), "http".into()],
+        };
+        let encoded = codec.encode(&data)?;
+        assert_eq!(codec.decode::<Data>(&encoded)?, data);
+        Ok(())
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json() -> Result<()> {
+        roundtrip(Json)
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_cbor() -> Result<()> {
+        roundtrip(Cbor)
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_msgpack() -> Result<()> {
+        roundtrip(MsgPack)
+    }
+
+    #[cfg(feature = "xml")]
+    #[test]
+    fn test_xml() -> Result<()> {
+        assert_eq!(
+            Xml.encode(&Data {
+                name: "waki".into(),
+                count: 1,
+                tags: vec!["a".into()],
+            })?,
+            b"<Data><name>waki</name><count>1</count><tags>a</tags></Data>"
+        );
+        roundtrip(Xml)
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_toml() -> Result<()> {
+        roundtrip(Toml)
+    }
+}
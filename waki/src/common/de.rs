@@ -0,0 +1,738 @@
+//! A serde deserializer for key-value pairs, such as query strings, form bodies and multipart forms.
+
+#[cfg(feature = "multipart")]
+use crate::multipart::{Part, UPLOADED_FILE};
+
+use serde::de::{
+    self, value::StringDeserializer, DeserializeOwned, DeserializeSeed, EnumAccess,
+    IntoDeserializer, MapAccess, SeqAccess, VariantAccess, Visitor,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::vec;
+
+// The maximum number of nested keys, e.g. `a[b][c][d][e][f]`, as in serde_qs.
+const MAX_DEPTH: usize = 5;
+
+pub(crate) enum Value {
+    Text(String),
+    #[cfg(feature = "multipart")]
+    File(Box<Part>),
+    #[cfg(feature = "multipart")]
+    Bytes(Vec<u8>),
+    Seq(Vec<Value>),
+    Map(Vec<(String, Value)>),
+}
+
+/// Deserialize an `application/x-www-form-urlencoded` string.
+pub(crate) fn from_urlencoded<T: DeserializeOwned>(input: &[u8]) -> Result<T, Error> {
+    from_pairs(
+        form_urlencoded::parse(input).map(|(k, v)| (k.into_owned(), Value::Text(v.into_owned()))),
+    )
+}
+
+/// Deserialize the pairs as a map.
+///
+/// The values of repeated keys are collected into a sequence, and nested keys such as `a[b][c]`
+/// into nested maps, up to 5 levels deep. `a[]` is the same as `a`.
+pub(crate) fn from_pairs<T, I>(pairs: I) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+    I: IntoIterator<Item = (String, Value)>,
+{
+    let mut fields = Fields::default();
+    for (key, value) in pairs {
+        let path = parse_key(&key);
+        if path.len() > MAX_DEPTH + 1 {
+            let message = format!("nested deeper than {MAX_DEPTH} levels");
+            return Err(<Error as de::Error>::custom(message).at(&key));
+        }
+        fields.insert(&path, value)?;
+    }
+    T::deserialize(fields.into_value())
+}
+
+// Split `a[b][c]` into `a`, `b` and `c`, keys that are not well-formed are used as is.
+fn parse_key(key: &str) -> Vec<&str> {
+    let Some(open) = key.find('[').filter(|open| *open > 0) else {
+        return vec![key];
+    };
+    let mut path = vec![&key[..open]];
+    let mut rest = &key[open..];
+    while let Some(inner) = rest.strip_prefix('[') {
+        let Some(close) = inner.find(']') else {
+            return vec![key];
+        };
+        path.push(&inner[..close]);
+        rest = &inner[close + 1..];
+    }
+    if !rest.is_empty() {
+        return vec![key];
+    }
+    if path.last() == Some(&"") {
+        path.pop();
+    }
+    path
+}
+
+// A map being built from the pairs, indexed by key to keep the insertion linear.
+#[derive(Default)]
+struct Fields {
+    entries: Vec<(String, Field)>,
+    index: HashMap<String, usize>,
+}
+
+enum Field {
+    Value(Value),
+    Map(Fields),
+}
+
+impl Fields {
+    fn insert(&mut self, path: &[&str], value: Value) -> Result<(), Error> {
+        let Some((key, rest)) = path.split_first() else {
+            return Ok(());
+        };
+        let existing = match self.index.get(*key) {
+            Some(&i) => Some(&mut self.entries[i].1),
+            None => None,
+        };
+        if rest.is_empty() {
+            match existing {
+                Some(Field::Value(Value::Seq(values))) => values.push(value),
+                Some(Field::Value(existing)) => {
+                    let first = std::mem::replace(existing, Value::Seq(vec![]));
+                    *existing = Value::Seq(vec![first, value]);
+                }
+                Some(Field::Map(_)) => return Err(conflict(key)),
+                None => self.push(key, Field::Value(value)),
+            }
+            return Ok(());
+        }
+        match existing {
+            Some(Field::Map(nested)) => nested.insert(rest, value),
+            Some(Field::Value(_)) => Err(conflict(key)),
+            None => {
+                let mut nested = Fields::default();
+                nested.insert(rest, value)?;
+                self.push(key, Field::Map(nested));
+                Ok(())
+            }
+        }
+    }
+
+    fn push(&mut self, key: &str, field: Field) {
+        self.index.insert(key.to_string(), self.entries.len());
+        self.entries.push((key.to_string(), field));
+    }
+
+    fn into_value(self) -> Value {
+        let entries = self.entries.into_iter().map(|(key, field)| match field {
+            Field::Value(value) => (key, value),
+            Field::Map(nested) => (key, nested.into_value()),
+        });
+        Value::Map(entries.collect())
+    }
+}
+
+fn conflict(key: &str) -> Error {
+    <Error as de::Error>::custom("found both a value and nested fields").at(key)
+}
+
+#[derive(Debug)]
+pub(crate) struct Error {
+    // the path of the field that failed, outermost first
+    path: Vec<String>,
+    message: String,
+}
+
+impl Error {
+    fn at(mut self, key: &str) -> Self {
+        self.path.insert(0, key.to_string());
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((first, rest)) = self.path.split_first() {
+            write!(f, "invalid field `{first}")?;
+            for key in rest {
+                write!(f, "[{key}]")?;
+            }
+            write!(f, "`: ")?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self {
+            path: vec![],
+            message: msg.to_string(),
+        }
+    }
+}
+
+impl Value {
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Text(_) => "a text value",
+            #[cfg(feature = "multipart")]
+            Value::File(part) if part.filename.is_none() => "a text value",
+            #[cfg(feature = "multipart")]
+            Value::File(_) => "a file",
+            #[cfg(feature = "multipart")]
+            Value::Bytes(_) => "binary data",
+            Value::Seq(_) => "multiple values",
+            Value::Map(_) => "nested fields",
+        }
+    }
+
+    fn into_text(self) -> Result<String, Error> {
+        match self {
+            Value::Text(text) => Ok(text),
+            // parts without a filename are text fields
+            #[cfg(feature = "multipart")]
+            Value::File(part) if part.filename.is_none() => String::from_utf8(part.value)
+                .map_err(|_| de::Error::custom("expected a text value, found invalid UTF-8")),
+            Value::Seq(mut values) if values.len() == 1 => values.remove(0).into_text(),
+            value => Err(de::Error::custom(format_args!(
+                "expected a single text value, found {}",
+                value.kind()
+            ))),
+        }
+    }
+
+    fn parse<T>(self) -> Result<T, Error>
+    where
+        T: std::str::FromStr,
+        T::Err: fmt::Display,
+    {
+        let text = self.into_text()?;
+        text.parse()
+            .map_err(|e| de::Error::custom(format_args!("invalid value {text:?}: {e}")))
+    }
+
+    #[cfg(feature = "multipart")]
+    fn into_file(part: Box<Part>) -> Value {
+        let mut entries = vec![];
+        if let Some(filename) = part.filename {
+            entries.push(("filename".to_string(), Value::Text(filename)));
+        }
+        if let Some(mime) = part.mime {
+            entries.push(("content_type".to_string(), Value::Text(mime.to_string())));
+        }
+        entries.push(("data".to_string(), Value::Bytes(part.value)));
+        Value::Map(entries)
+    }
+}
+
+macro_rules! deserialize_parse {
+    ($($method:ident => $visit:ident,)*) => ($(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            visitor.$visit(self.parse()?)
+        }
+    )*)
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Text(text) => visitor.visit_string(text),
+            #[cfg(feature = "multipart")]
+            Value::File(part) => match part.filename {
+                Some(_) => Value::into_file(part).deserialize_any(visitor),
+                None => visitor.visit_string(Value::File(part).into_text()?),
+            },
+            #[cfg(feature = "multipart")]
+            Value::Bytes(data) => visitor.visit_byte_buf(data),
+            Value::Seq(values) => visitor.visit_seq(SeqDeserializer::new(values)),
+            Value::Map(entries) => visitor.visit_map(MapDeserializer::new(entries)),
+        }
+    }
+
+    deserialize_parse! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let text = self.into_text()?;
+        // checkboxes are sent as `on`
+        match text.as_str() {
+            "true" | "on" | "1" => visitor.visit_bool(true),
+            "false" | "off" | "0" => visitor.visit_bool(false),
+            _ => Err(de::Error::custom(format_args!(
+                "invalid value {text:?}: expected a boolean"
+            ))),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_string(self.into_text()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_string(self.into_text()?)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_string(self.into_text()?)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            #[cfg(feature = "multipart")]
+            Value::File(part) => visitor.visit_byte_buf(part.value),
+            #[cfg(feature = "multipart")]
+            Value::Bytes(data) => visitor.visit_byte_buf(data),
+            value => visitor.visit_byte_buf(value.into_text()?.into_bytes()),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            // empty inputs are sent as empty values
+            Value::Text(text) if text.is_empty() => visitor.visit_none(),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Seq(values) => visitor.visit_seq(SeqDeserializer::new(values)),
+            // `a[0]=x&a[1]=y`
+            Value::Map(entries) => {
+                let mut indexed = entries
+                    .into_iter()
+                    .map(|(key, value)| match key.parse::<usize>() {
+                        Ok(i) => Ok((i, value)),
+                        Err(_) => Err(de::Error::custom(
+                            "expected a sequence, found nested fields",
+                        )),
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+                indexed.sort_by_key(|(i, _)| *i);
+                let values = indexed.into_iter().map(|(_, value)| value).collect();
+                visitor.visit_seq(SeqDeserializer::new(values))
+            }
+            // a single value is a sequence of one
+            value => visitor.visit_seq(SeqDeserializer::new(vec![value])),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Map(entries) => visitor.visit_map(MapDeserializer::new(entries)),
+            value => Err(de::Error::custom(format_args!(
+                "expected nested fields, found {}",
+                value.kind()
+            ))),
+        }
+    }
+
+    #[cfg_attr(not(feature = "multipart"), allow(unused_variables))]
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self {
+            #[cfg(feature = "multipart")]
+            Value::File(part) if name == UPLOADED_FILE => {
+                Value::into_file(part).deserialize_map(visitor)
+            }
+            #[cfg(feature = "multipart")]
+            value if name == UPLOADED_FILE => Err(de::Error::custom(format_args!(
+                "expected a file, found {}",
+                value.kind()
+            ))),
+            value => value.deserialize_map(visitor),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self {
+            Value::Map(mut entries) if entries.len() == 1 => {
+                let (variant, value) = entries.remove(0);
+                visitor.visit_enum(Enum { variant, value })
+            }
+            value => visitor.visit_enum(Enum {
+                variant: value.into_text()?,
+                value: Value::Map(vec![]),
+            }),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+}
+
+struct SeqDeserializer {
+    iter: std::iter::Enumerate<vec::IntoIter<Value>>,
+}
+
+impl SeqDeserializer {
+    fn new(values: Vec<Value>) -> Self {
+        Self {
+            iter: values.into_iter().enumerate(),
+        }
+    }
+}
+
+impl<'de> SeqAccess<'de> for SeqDeserializer {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        match self.iter.next() {
+            Some((i, value)) => seed
+                .deserialize(value)
+                .map(Some)
+                .map_err(|e| e.at(&i.to_string())),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct MapDeserializer {
+    iter: vec::IntoIter<(String, Value)>,
+    // the entry whose key has been returned by `next_key_seed`
+    entry: Option<(String, Value)>,
+}
+
+impl MapDeserializer {
+    fn new(entries: Vec<(String, Value)>) -> Self {
+        Self {
+            iter: entries.into_iter(),
+            entry: None,
+        }
+    }
+}
+
+impl<'de> MapAccess<'de> for MapDeserializer {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        match self.iter.next() {
+            Some((key, value)) => {
+                // keys are deserialized as text values, so maps with numeric keys are supported
+                let k = seed.deserialize(Value::Text(key.clone()))?;
+                self.entry = Some((key, value));
+                Ok(Some(k))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (key, value) = self
+            .entry
+            .take()
+            .ok_or_else(|| de::Error::custom("value is missing"))?;
+        seed.deserialize(value).map_err(|e| e.at(&key))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct Enum {
+    variant: String,
+    value: Value,
+}
+
+impl<'de> EnumAccess<'de> for Enum {
+    type Error = Error;
+    type Variant = Value;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Value), Error> {
+        let deserializer: StringDeserializer<Error> = self.variant.into_deserializer();
+        Ok((seed.deserialize(deserializer)?, self.value))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Value {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "multipart")]
+    use crate::multipart::{Multipart, Part, UploadedFile};
+    use anyhow::Result;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Filter {
+        name: String,
+        min: Option<u32>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Search {
+        q: String,
+        page: Option<u32>,
+        tag: Vec<String>,
+        filter: Filter,
+        ids: Vec<u64>,
+        sort: HashMap<String, String>,
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(parse_key("a"), ["a"]);
+        assert_eq!(parse_key("a[b][c]"), ["a", "b", "c"]);
+        assert_eq!(parse_key("a[]"), ["a"]);
+        assert_eq!(parse_key("a[b][]"), ["a", "b"]);
+        assert_eq!(parse_key("a[b"), ["a[b"]);
+        assert_eq!(parse_key("a[b]c"), ["a[b]c"]);
+        assert_eq!(parse_key("[a]"), ["[a]"]);
+    }
+
+    #[test]
+    fn test_urlencoded() -> Result<()> {
+        let search: Search = from_urlencoded(
+            b"q=rust+wasi&page=&tag=a&tag[]=b&filter[name]=x&filter[min]=3&ids[1]=20&ids[0]=10&sort[date]=desc",
+        )?;
+        assert_eq!(
+            search,
+            Search {
+                q: "rust wasi".into(),
+                page: None,
+                tag: vec!["a".into(), "b".into()],
+                filter: Filter {
+                    name: "x".into(),
+                    min: Some(3),
+                },
+                ids: vec![10, 20],
+                sort: HashMap::from([("date".into(), "desc".into())]),
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_urlencoded_errors() {
+        let error = |input: &str| {
+            from_urlencoded::<Search>(input.as_bytes())
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error("q=a&tag=a&filter[name]=x&ids=1&sort[a]=b&filter[min]=-1"),
+            "invalid field `filter[min]`: invalid value \"-1\": invalid digit found in string"
+        );
+        assert_eq!(
+            error("q=a&tag=a&filter[name]=x&ids=1&ids=b&sort[a]=b"),
+            "invalid field `ids[1]`: invalid value \"b\": invalid digit found in string"
+        );
+        assert_eq!(
+            error("q=a&q[b]=c"),
+            "invalid field `q`: found both a value and nested fields"
+        );
+        assert_eq!(
+            error("q=a&a[b][c][d][e][f][g]=h"),
+            "invalid field `a[b][c][d][e][f][g]`: nested deeper than 5 levels"
+        );
+        assert_eq!(
+            error("q=a&tag=a&ids=1&sort[a]=b&filter=x"),
+            "invalid field `filter`: expected nested fields, found a text value"
+        );
+        assert_eq!(
+            error("q=a&tag=a&ids=1&sort[a]=b&filter[min]=1"),
+            "invalid field `filter`: missing field `name`"
+        );
+    }
+
+    #[cfg(feature = "multipart")]
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Visibility {
+        Public,
+        Private,
+    }
+
+    #[cfg(feature = "multipart")]
+    #[derive(Debug, Deserialize)]
+    struct Upload {
+        title: String,
+        count: u32,
+        public: bool,
+        visibility: Visibility,
+        tags: Vec<String>,
+        note: Option<String>,
+        avatar: UploadedFile,
+        files: Vec<UploadedFile>,
+    }
+
+    #[cfg(feature = "multipart")]
+    #[test]
+    fn test_multipart() -> Result<()> {
+        let multipart = Multipart::from_iter([
+            Part::new("title", "hello"),
+            Part::new("count", "42"),
+            Part::new("public", "on"),
+            Part::new("visibility", "private"),
+            Part::new("tags", "a"),
+            Part::new("avatar", "png")
+                .filename("a.png")
+                .mime(mime::IMAGE_PNG),
+            Part::new("tags", "b"),
+            Part::new("files", "1").filename("1.txt"),
+        ]);
+        let upload: Upload = multipart.deserialize()?;
+        assert_eq!(upload.title, "hello");
+        assert_eq!(upload.count, 42);
+        assert!(upload.public);
+        assert_eq!(upload.visibility, Visibility::Private);
+        assert_eq!(upload.tags, ["a", "b"]);
+        assert_eq!(upload.note, None);
+        assert_eq!(
+            upload.avatar,
+            UploadedFile {
+                filename: Some("a.png".into()),
+                mime: Some(mime::IMAGE_PNG),
+                data: b"png".to_vec(),
+            }
+        );
+        assert_eq!(upload.files.len(), 1);
+        assert_eq!(upload.files[0].filename.as_deref(), Some("1.txt"));
+        Ok(())
+    }
+
+    #[cfg(feature = "multipart")]
+    #[test]
+    fn test_multipart_errors() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Form {
+            count: u32,
+            file: Option<UploadedFile>,
+        }
+
+        let error = |parts: Vec<Part>| {
+            Multipart::from_iter(parts)
+                .deserialize::<Form>()
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(error(vec![]), "missing field `count`");
+        assert_eq!(
+            error(vec![Part::new("count", "ten")]),
+            "invalid field `count`: invalid value \"ten\": invalid digit found in string"
+        );
+        assert_eq!(
+            error(vec![Part::new("count", "1"), Part::new("count", "2")]),
+            "invalid field `count`: expected a single text value, found multiple values"
+        );
+        assert_eq!(
+            error(vec![Part::new("count", "1").filename("count.txt")]),
+            "invalid field `count`: expected a single text value, found a file"
+        );
+        assert_eq!(
+            error(vec![
+                Part::new("count", "1"),
+                Part::new("file", "a").filename("a.txt"),
+                Part::new("file", "b").filename("b.txt")
+            ]),
+            "invalid field `file`: expected a file, found multiple values"
+        );
+    }
+}
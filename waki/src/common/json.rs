@@ -0,0 +1,301 @@
+use anyhow::{anyhow, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use std::io::{self, BufRead, Read};
+use std::marker::PhantomData;
+
+#[derive(Clone, Copy)]
+pub(crate) enum Format {
+    /// One value per line.
+    Lines,
+    /// A single JSON array.
+    Array,
+}
+
+/// Serializes the items one at a time as they are read, so that the whole body is never
+/// buffered in memory.
+pub(crate) struct JsonWriter<I> {
+    items: I,
+    format: Format,
+    buf: Vec<u8>,
+    pos: usize,
+    started: bool,
+    done: bool,
+}
+
+impl<I> JsonWriter<I> {
+    pub(crate) fn new(items: I, format: Format) -> Self {
+        Self {
+            items,
+            format,
+            buf: vec![],
+            pos: 0,
+            started: false,
+            done: false,
+        }
+    }
+}
+
+impl<I> JsonWriter<I>
+where
+    I: Iterator,
+    I::Item: Serialize,
+{
+    fn fill(&mut self) -> io::Result<()> {
+        self.buf.clear();
+        self.pos = 0;
+        match (self.items.next(), self.format) {
+            (Some(item), Format::Lines) => {
+                serde_json::to_writer(&mut self.buf, &item)?;
+                self.buf.push(b'\n');
+            }
+            (Some(item), Format::Array) => {
+                self.buf.push(if self.started { b',' } else { b'[' });
+                serde_json::to_writer(&mut self.buf, &item)?;
+            }
+            (None, Format::Lines) => self.done = true,
+            (None, Format::Array) => {
+                if !self.started {
+                    self.buf.push(b'[');
+                }
+                self.buf.push(b']');
+                self.done = true;
+            }
+        }
+        self.started = true;
+        Ok(())
+    }
+}
+
+impl<I> Read for JsonWriter<I>
+where
+    I: Iterator,
+    I::Item: Serialize,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.buf.len() {
+            if self.done {
+                return Ok(0);
+            }
+            self.fill()?;
+        }
+        let n = buf.len().min(self.buf.len() - self.pos);
+        buf[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Deserializes the elements of a JSON array one at a time, only the raw bytes of the
+/// current element are buffered.
+pub(crate) struct JsonArrayReader<R, T> {
+    reader: R,
+    started: bool,
+    done: bool,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<R: BufRead, T: DeserializeOwned> JsonArrayReader<R, T> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            reader,
+            started: false,
+            done: false,
+            _marker: PhantomData,
+        }
+    }
+
+    fn peek(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.reader.fill_buf()?.first().copied())
+    }
+
+    /// Skip whitespace, then peek the next byte.
+    fn peek_token(&mut self) -> io::Result<Option<u8>> {
+        while let Some(b) = self.peek()? {
+            if !b.is_ascii_whitespace() {
+                return Ok(Some(b));
+            }
+            self.reader.consume(1);
+        }
+        Ok(None)
+    }
+
+    fn next_element(&mut self) -> Result<Option<T>> {
+        let expected = if self.started { b',' } else { b'[' };
+        match self.peek_token()? {
+            Some(b']') if self.started => {
+                self.done = true;
+                return Ok(None);
+            }
+            Some(b) if b == expected => self.reader.consume(1),
+            Some(b) => {
+                return Err(anyhow!(
+                    "expected `{}`, found `{}`",
+                    expected as char,
+                    b as char
+                ))
+            }
+            None => return Err(anyhow!("unexpected end of JSON array")),
+        }
+        let first = self.peek_token()?;
+        if !self.started {
+            self.started = true;
+            if first == Some(b']') {
+                self.done = true;
+                return Ok(None);
+            }
+        }
+
+        // collect the element until the `,` or `]` following it
+        let mut element = vec![];
+        let mut depth = 0usize;
+        let mut in_string = false;
+        let mut escaped = false;
+        while let Some(b) = self.peek()? {
+            if !in_string && depth == 0 && (b == b',' || b == b']' || b.is_ascii_whitespace()) {
+                break;
+            }
+            self.reader.consume(1);
+            element.push(b);
+            if in_string {
+                match b {
+                    _ if escaped => escaped = false,
+                    b'\\' => escaped = true,
+                    b'"' => in_string = false,
+                    _ => {}
+                }
+            } else {
+                match b {
+                    b'"' => in_string = true,
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+            }
+        }
+        Ok(Some(serde_json::from_slice(&element)?))
+    }
+}
+
+impl<R: BufRead, T: DeserializeOwned> Iterator for JsonArrayReader<R, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.next_element() {
+            Ok(Some(element)) => Some(Ok(element)),
+            Ok(None) => None,
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::io::BufReader;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Record {
+        id: u64,
+        name: String,
+    }
+
+    fn records() -> Vec<Record> {
+        vec![
+            Record {
+                id: 1,
+                name: "a, [b]".into(),
+            },
+            Record {
+                id: 2,
+                name: "\"c\"\\".into(),
+            },
+        ]
+    }
+
+    fn write(format: Format, records: Vec<Record>) -> String {
+        let mut data = String::new();
+        // a tiny buffer to exercise partial reads
+        BufReader::with_capacity(3, JsonWriter::new(records.into_iter(), format))
+            .read_to_string(&mut data)
+            .unwrap();
+        data
+    }
+
+    fn read<T: DeserializeOwned>(data: &str) -> Vec<Result<T>> {
+        JsonArrayReader::new(BufReader::with_capacity(3, data.as_bytes())).collect()
+    }
+
+    #[test]
+    fn test_writer() {
+        assert_eq!(
+            write(Format::Lines, records()),
+            "{\"id\":1,\"name\":\"a, [b]\"}\n{\"id\":2,\"name\":\"\\\"c\\\"\\\\\"}\n"
+        );
+        assert_eq!(
+            write(Format::Array, records()),
+            r#"[{"id":1,"name":"a, [b]"},{"id":2,"name":"\"c\"\\"}]"#
+        );
+        assert_eq!(write(Format::Lines, vec![]), "");
+        assert_eq!(write(Format::Array, vec![]), "[]");
+    }
+
+    #[test]
+    fn test_reader() {
+        let data = write(Format::Array, records());
+        let result = read::<Record>(&data)
+            .into_iter()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(result, records());
+
+        let result = read::<serde_json::Value>(" [ 1 , \"x\" ,[2, {\"a\": [3]}], null,true ] ")
+            .into_iter()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            result,
+            serde_json::json!([1, "x", [2, {"a": [3]}], null, true])
+                .as_array()
+                .unwrap()
+                .clone()
+        );
+
+        assert!(read::<u64>("[]").is_empty());
+        assert!(read::<u64>(" [\n] ").is_empty());
+    }
+
+    #[test]
+    fn test_reader_errors() {
+        let result = read::<u64>("{}");
+        assert_eq!(result.len(), 1);
+        assert_eq!(
+            result[0].as_ref().unwrap_err().to_string(),
+            "expected `[`, found `{`"
+        );
+
+        let result = read::<u64>("[1, 2");
+        assert_eq!(result.len(), 3);
+        assert_eq!(
+            result[2].as_ref().unwrap_err().to_string(),
+            "unexpected end of JSON array"
+        );
+
+        let result = read::<u64>("[1 2]");
+        assert_eq!(
+            result[1].as_ref().unwrap_err().to_string(),
+            "expected `,`, found `2`"
+        );
+
+        // stops after the first invalid element
+        let result = read::<u64>(r#"[1, "a", 3]"#);
+        assert_eq!(result.len(), 2);
+        assert!(result[1].is_err());
+    }
+}
@@ -1,3 +1,7 @@
+pub(crate) mod de;
 mod header;
+#[cfg(feature = "json")]
+pub(crate) mod json;
 mod request_and_response;
 mod scheme;
+pub(crate) mod ser;
@@ -1,14 +1,20 @@
+#[cfg(feature = "json")]
+use crate::common::json::{Format, JsonArrayReader, JsonWriter};
 #[cfg(feature = "multipart")]
-use crate::multipart::{parser::parse, Form, Part};
+use crate::multipart::{Form, Multipart, MultipartConfig, MultipartError, MultipartReader};
 use crate::{
-    body::Body,
-    header::{AsHeaderName, HeaderMap, HeaderValue, IntoHeaderName, CONTENT_TYPE},
+    body::{body_limit, Body, BodyTooLarge},
+    codec::Codec,
+    common::{de, ser},
+    header::{AsHeaderName, HeaderMap, HeaderValue, IntoHeaderName, CONTENT_LENGTH, CONTENT_TYPE},
+    headers::Header,
     Request, RequestBuilder, Response, ResponseBuilder,
 };
-use anyhow::{anyhow, Error, Result};
+use anyhow::{Error, Result};
 use serde::Serialize;
 use std::borrow::Borrow;
 use std::collections::HashMap;
+use std::io::{self, BufRead, Read};
 
 macro_rules! impl_common_get_methods {
     ($($t:ty),+ $(,)?) => ($(
@@ -25,6 +31,82 @@ macro_rules! impl_common_get_methods {
                 &self.headers
             }
 
+            /// Get the typed header, `None` if it is missing or invalid.
+            ///
+            /// ```
+            /// # use waki::{headers::ContentLength, Request};
+            /// # fn run(req: Request) {
+            /// if let Some(ContentLength(len)) = req.typed_header::<ContentLength>() {
+            ///     println!("{}", len);
+            /// }
+            /// # }
+            /// ```
+            pub fn typed_header<H: Header>(&self) -> Option<H> {
+                let name = H::name();
+                if !self.headers.contains_key(&name) {
+                    return None;
+                }
+                H::decode(self.headers.get_all(name).iter()).ok()
+            }
+
+            /// Get the trailers.
+            ///
+            /// For incoming requests/responses, the trailers are sent after the body, so this should
+            /// be called once the body has been read (e.g. through the [`Read`] implementation),
+            /// any unread body data is discarded. To read the full body and the trailers at once,
+            /// use [`body_with_trailers`](Self::body_with_trailers).
+            ///
+            /// ```
+            /// # use anyhow::Result;
+            /// # use std::io::Read;
+            /// # use waki::Response;
+            /// # fn run() -> Result<()> {
+            /// # let mut r = Response::new();
+            /// let mut body = vec![];
+            /// r.read_to_end(&mut body)?;
+            /// if let Some(trailers) = r.trailers()? {
+            ///     println!("{:?}", trailers.get("checksum"));
+            /// }
+            /// # Ok(())
+            /// # }
+            /// ```
+            pub fn trailers(&mut self) -> Result<Option<&HeaderMap>> {
+                if let Some(trailers) = self.body.finish()? {
+                    self.trailers = Some(trailers);
+                }
+                Ok(self.trailers.as_ref())
+            }
+
+            /// Get the full body and the trailers that follow it.
+            ///
+            /// The body size is limited by the default limit, see [`set_body_limit`](crate::set_body_limit).
+            ///
+            /// ```
+            /// # use anyhow::Result;
+            /// # use waki::Response;
+            /// # fn run() -> Result<()> {
+            /// # let r = Response::new();
+            /// let (body, trailers) = r.body_with_trailers()?;
+            /// if let Some(trailers) = trailers {
+            ///     println!("{} bytes, checksum {:?}", body.len(), trailers.get("checksum"));
+            /// }
+            /// # Ok(())
+            /// # }
+            /// ```
+            pub fn body_with_trailers(mut self) -> Result<(Vec<u8>, Option<HeaderMap>)> {
+                let limit = body_limit();
+                check_content_length(&self.headers, limit)?;
+                let mut body = vec![];
+                (&mut self.body)
+                    .take(limit.saturating_add(1))
+                    .read_to_end(&mut body)?;
+                if body.len() as u64 > limit {
+                    return Err(BodyTooLarge::new(limit).into());
+                }
+                let trailers = self.trailers()?.cloned();
+                Ok((body, trailers))
+            }
+
             /// Get a chunk of the body.
             ///
             /// It will block until at least one byte can be read or the stream is closed.
@@ -39,9 +121,34 @@ macro_rules! impl_common_get_methods {
             /// Get the full body.
             ///
             /// It will block until the stream is closed.
+            ///
+            /// To process the body without buffering all of it, use the [`Read`] and [`BufRead`]
+            /// implementations instead.
+            ///
+            /// The body size is limited by the default limit, see [`set_body_limit`](crate::set_body_limit).
             #[inline]
             pub fn body(self) -> Result<Vec<u8>> {
-                self.body.bytes()
+                self.body_limited(body_limit())
+            }
+
+            /// Get the full body, failing with [`BodyTooLarge`] if it is larger than `limit` bytes.
+            ///
+            /// If the `Content-Length` header exceeds the limit, it fails without reading the body.
+            ///
+            /// ```
+            /// # use waki::{BodyTooLarge, ErrorCode, Request, Response};
+            /// fn handler(req: Request) -> Result<Response, ErrorCode> {
+            ///     let body = req.body_limited(10 << 20).map_err(|e| match e.downcast::<BodyTooLarge>() {
+            ///         // will be answered with a 413 response
+            ///         Ok(e) => e.into(),
+            ///         Err(e) => ErrorCode::InternalError(Some(e.to_string())),
+            ///     })?;
+            ///     Response::builder().body(body).build()
+            /// }
+            /// ```
+            pub fn body_limited(self, limit: u64) -> Result<Vec<u8>> {
+                check_content_length(&self.headers, limit)?;
+                self.body.bytes_limited(limit)
             }
 
             /// Deserialize the body as JSON.
@@ -71,35 +178,255 @@ macro_rules! impl_common_get_methods {
                 Ok(serde_json::from_slice(self.body()?.as_ref())?)
             }
 
+            /// Decode the body with the codec.
+            ///
+            /// ```
+            /// # use anyhow::Result;
+            /// # use serde::Deserialize;
+            /// # use waki::Response;
+            /// # #[cfg(feature = "cbor")]
+            /// # fn run() -> Result<()> {
+            /// # let r = Response::new();
+            /// #[derive(Deserialize)]
+            /// struct Data {
+            ///     origin: String,
+            /// }
+            ///
+            /// let data: Data = r.decode(waki::codec::Cbor)?;
+            /// # Ok(())
+            /// # }
+            /// ```
+            pub fn decode<C: Codec, T: serde::de::DeserializeOwned>(self, codec: C) -> Result<T> {
+                codec.decode(&self.body()?)
+            }
+
+            /// Deserialize the body as CBOR.
+            ///
+            /// # Optional
+            ///
+            /// This requires the `cbor` feature enabled.
+            #[cfg(feature = "cbor")]
+            pub fn cbor<T: serde::de::DeserializeOwned>(self) -> Result<T> {
+                self.decode(crate::codec::Cbor)
+            }
+
+            /// Deserialize the body as MessagePack.
+            ///
+            /// # Optional
+            ///
+            /// This requires the `msgpack` feature enabled.
+            #[cfg(feature = "msgpack")]
+            pub fn msgpack<T: serde::de::DeserializeOwned>(self) -> Result<T> {
+                self.decode(crate::codec::MsgPack)
+            }
+
+            /// Deserialize the body as XML.
+            ///
+            /// # Optional
+            ///
+            /// This requires the `xml` feature enabled.
+            #[cfg(feature = "xml")]
+            pub fn xml<T: serde::de::DeserializeOwned>(self) -> Result<T> {
+                self.decode(crate::codec::Xml)
+            }
+
+            /// Deserialize the body as TOML.
+            ///
+            /// # Optional
+            ///
+            /// This requires the `toml` feature enabled.
+            #[cfg(feature = "toml")]
+            pub fn toml<T: serde::de::DeserializeOwned>(self) -> Result<T> {
+                self.decode(crate::codec::Toml)
+            }
+
+            /// Deserialize the body as newline-delimited JSON, returning an iterator over the values.
+            ///
+            /// The body is read incrementally, blank lines are skipped.
+            ///
+            /// # Optional
+            ///
+            /// This requires the `json` feature enabled.
+            ///
+            /// ```
+            /// # use anyhow::Result;
+            /// # use serde::Deserialize;
+            /// # use waki::Response;
+            /// # fn run() -> Result<()> {
+            /// # let r = Response::new();
+            /// #[derive(Deserialize)]
+            /// struct Record {
+            ///     id: u64,
+            /// }
+            ///
+            /// for record in r.ndjson::<Record>() {
+            ///     println!("{}", record?.id);
+            /// }
+            /// # Ok(())
+            /// # }
+            /// ```
+            #[cfg(feature = "json")]
+            pub fn ndjson<T: serde::de::DeserializeOwned>(self) -> impl Iterator<Item = Result<T>> {
+                self.lines().filter_map(|line| match line {
+                    Ok(line) if line.trim().is_empty() => None,
+                    Ok(line) => Some(serde_json::from_str(&line).map_err(Error::from)),
+                    Err(e) => Some(Err(e.into())),
+                })
+            }
+
+            /// Deserialize the body as a JSON array, returning an iterator over the elements.
+            ///
+            /// The body is read incrementally, so large arrays can be processed without buffering
+            /// the whole body. The iteration stops after the first error.
+            ///
+            /// # Optional
+            ///
+            /// This requires the `json` feature enabled.
+            ///
+            /// ```
+            /// # use anyhow::Result;
+            /// # use serde::Deserialize;
+            /// # use waki::Response;
+            /// # fn run() -> Result<()> {
+            /// # let r = Response::new();
+            /// #[derive(Deserialize)]
+            /// struct Record {
+            ///     id: u64,
+            /// }
+            ///
+            /// for record in r.json_array::<Record>() {
+            ///     println!("{}", record?.id);
+            /// }
+            /// # Ok(())
+            /// # }
+            /// ```
+            #[cfg(feature = "json")]
+            pub fn json_array<T: serde::de::DeserializeOwned>(self) -> impl Iterator<Item = Result<T>> {
+                JsonArrayReader::new(self)
+            }
+
             /// Parse the body as form data.
             pub fn form(self) -> Result<HashMap<String, String>> {
                 Ok(form_urlencoded::parse(self.body()?.as_ref()).into_owned().collect())
             }
 
-            /// Parse the body as multipart/form-data.
+            /// Deserialize the form data body into `T`.
+            ///
+            /// Repeated keys can be collected into a `Vec`, and nested keys such as `a[b]=c` into
+            /// nested structs or maps, see [`Request::query_as`].
+            pub fn form_as<T: serde::de::DeserializeOwned>(self) -> Result<T> {
+                Ok(de::from_urlencoded(&self.body()?)?)
+            }
+
+            /// Parse the body as multipart, e.g. multipart/form-data, multipart/mixed or
+            /// multipart/byteranges.
+            ///
+            /// All parts are kept in order, including multiple parts with the same name.
             ///
             /// # Optional
             ///
             /// This requires the `multipart` feature enabled.
             #[cfg(feature = "multipart")]
-            pub fn multipart(self) -> Result<HashMap<String, Part>> {
-                match self.headers.get(CONTENT_TYPE) {
-                    Some(header) => {
-                        let mime = header.to_str()?.parse::<mime::Mime>()?;
-                        let boundary = match mime.get_param(mime::BOUNDARY) {
-                            Some(v) => v.as_str(),
-                            None => {
-                                return Err(anyhow!(
-                                    "unable to find the boundary value in the Content-Type header"
-                                ))
-                            }
-                        };
-                        parse(self.body()?.as_ref(), boundary)
-                    }
-                    None => Err(anyhow!(
-                        "parse body as multipart failed, unable to find the Content-Type header"
-                    )),
-                }
+            pub fn multipart(self) -> Result<Multipart> {
+                self.multipart_with(&MultipartConfig::default())
+            }
+
+            /// Parse the body as multipart, enforcing the limits and validation rules
+            /// of the config.
+            ///
+            /// Violations are reported as [`MultipartError`](crate::multipart::MultipartError),
+            /// which can be mapped to a status code:
+            ///
+            /// ```
+            /// # use waki::{multipart::{MultipartConfig, MultipartError}, Request, Response};
+            /// # fn handler(req: Request) -> Response {
+            /// let config = MultipartConfig::new().max_file_bytes(1 << 20).required("file");
+            /// match req.multipart_with(&config) {
+            ///     Ok(form) => Response::new(),
+            ///     Err(e) => {
+            ///         let status = e.downcast_ref::<MultipartError>().map_or(400, |e| e.status_code());
+            ///         Response::builder().status_code(status).body(e.to_string()).build().unwrap()
+            ///     }
+            /// }
+            /// # }
+            /// ```
+            ///
+            /// The body size limit set by [`set_body_limit`](crate::set_body_limit) is applied
+            /// unless the config sets a lower one. A larger body fails with
+            /// [`MultipartError::BodyTooLarge`](crate::multipart::MultipartError::BodyTooLarge),
+            /// without reading it if the `Content-Length` header exceeds the limit.
+            ///
+            /// # Optional
+            ///
+            /// This requires the `multipart` feature enabled.
+            #[cfg(feature = "multipart")]
+            pub fn multipart_with(self, config: &MultipartConfig) -> Result<Multipart> {
+                let limit = config
+                    .max_total_bytes
+                    .map_or(body_limit(), |max| max.min(body_limit()));
+                check_content_length(&self.headers, limit).map_err(MultipartError::from)?;
+                self.multipart_reader()?
+                    .config(config.clone().max_total_bytes(limit))
+                    .into_multipart()
+            }
+
+            /// Parse the body as multipart and deserialize the fields into `T`.
+            ///
+            /// See [`Multipart::deserialize`] for how the fields are mapped.
+            ///
+            /// # Optional
+            ///
+            /// This requires the `multipart` feature enabled.
+            #[cfg(feature = "multipart")]
+            pub fn multipart_into<T: serde::de::DeserializeOwned>(self) -> Result<T> {
+                self.multipart()?.deserialize()
+            }
+
+            /// Parse the body as multipart incrementally, without buffering the whole body.
+            ///
+            /// The body size limit set by [`set_body_limit`](crate::set_body_limit) is applied
+            /// unless a [`MultipartConfig`] with a maximum total size is set on the reader.
+            ///
+            /// # Optional
+            ///
+            /// This requires the `multipart` feature enabled.
+            #[cfg(feature = "multipart")]
+            pub fn multipart_reader(self) -> Result<MultipartReader<Self>> {
+                let boundary = multipart_boundary(&self.headers)?;
+                Ok(MultipartReader::new(self, &boundary).body_limit(body_limit()))
+            }
+        }
+
+        /// Read the body incrementally.
+        ///
+        /// ```
+        /// # use anyhow::Result;
+        /// # use std::io::BufRead;
+        /// # use waki::Response;
+        /// # fn run() -> Result<()> {
+        /// # let r = Response::new();
+        /// for line in r.lines() {
+        ///     println!("{}", line?);
+        /// }
+        /// # Ok(())
+        /// # }
+        /// ```
+        impl Read for $t {
+            #[inline]
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.body.read(buf)
+            }
+        }
+
+        impl BufRead for $t {
+            #[inline]
+            fn fill_buf(&mut self) -> io::Result<&[u8]> {
+                self.body.fill_buf()
+            }
+
+            #[inline]
+            fn consume(&mut self, amt: usize) {
+                self.body.consume(amt)
             }
         }
     )+)
@@ -107,10 +434,40 @@ macro_rules! impl_common_get_methods {
 
 impl_common_get_methods!(Request, Response);
 
+// Fail before reading a body whose `Content-Length` exceeds the limit.
+fn check_content_length(headers: &HeaderMap, limit: u64) -> Result<(), BodyTooLarge> {
+    let content_length = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    match content_length {
+        Some(len) if len > limit => Err(BodyTooLarge::new(limit)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(feature = "multipart")]
+fn multipart_boundary(headers: &HeaderMap) -> Result<String> {
+    match headers.get(CONTENT_TYPE) {
+        Some(header) => {
+            let mime = header.to_str()?.parse::<mime::Mime>()?;
+            match mime.get_param(mime::BOUNDARY) {
+                Some(v) => Ok(v.to_string()),
+                None => Err(anyhow::anyhow!(
+                    "unable to find the boundary value in the Content-Type header"
+                )),
+            }
+        }
+        None => Err(anyhow::anyhow!(
+            "parse body as multipart failed, unable to find the Content-Type header"
+        )),
+    }
+}
+
 macro_rules! impl_common_set_methods {
     ($($t:ty),+ $(,)?) => ($(
         impl $t {
-            /// Add a header.
+            /// Set a header, replacing any existing values of the header.
             ///
             /// ```
             /// # use waki::ResponseBuilder;
@@ -140,6 +497,67 @@ macro_rules! impl_common_set_methods {
                 self
             }
 
+            /// Append a header, keeping the existing values of the header.
+            ///
+            /// ```
+            /// # use waki::ResponseBuilder;
+            /// # fn run() {
+            /// # let r = ResponseBuilder::new();
+            /// r.append_header("Set-Cookie", "a=1").append_header("Set-Cookie", "b=2");
+            /// # }
+            /// ```
+            pub fn append_header<K, V>(mut self, key: K, value: V) -> Self
+            where
+                K: IntoHeaderName,
+                V: TryInto<HeaderValue>,
+                <V as TryInto<HeaderValue>>::Error: Into<Error>,
+            {
+                let mut err = None;
+                if let Ok(ref mut inner) = self.inner {
+                    match value.try_into().map_err(|e| e.into()) {
+                        Ok(v) => {
+                            inner.headers.append(key, v);
+                        }
+                        Err(e) => err = Some(e),
+                    };
+                }
+                if let Some(e) = err {
+                    self.inner = Err(e);
+                }
+                self
+            }
+
+            /// Remove all values of the header.
+            #[inline]
+            pub fn remove_header<K: AsHeaderName>(mut self, key: K) -> Self {
+                if let Ok(ref mut inner) = self.inner {
+                    inner.headers.remove(key);
+                }
+                self
+            }
+
+            /// Set all headers of the map, including multiple values of the same header.
+            ///
+            /// The existing values of the headers in the map are replaced, other headers are kept.
+            ///
+            /// ```
+            /// # use waki::{header::{HeaderMap, HeaderValue, LINK}, ResponseBuilder};
+            /// # fn run() {
+            /// # let r = ResponseBuilder::new();
+            /// let mut headers = HeaderMap::new();
+            /// headers.append(LINK, HeaderValue::from_static("</style.css>; rel=preload"));
+            /// headers.append(LINK, HeaderValue::from_static("</app.js>; rel=preload"));
+            /// r.header_map(headers);
+            /// # }
+            /// ```
+            #[inline]
+            pub fn header_map(mut self, headers: HeaderMap) -> Self {
+                if let Ok(ref mut inner) = self.inner {
+                    inner.headers.extend(headers);
+                }
+                self
+            }
+
             /// Add a set of headers.
             ///
             /// ```
@@ -176,6 +594,43 @@ macro_rules! impl_common_set_methods {
                 self
             }
 
+            /// Add a set of trailers, which are sent after the body.
+            ///
+            /// ```
+            /// # use waki::ResponseBuilder;
+            /// # fn run() {
+            /// # let r = ResponseBuilder::new();
+            /// r.trailers([("grpc-status", "0")]);
+            /// # }
+            /// ```
+            pub fn trailers<K, V, I>(mut self, trailers: I) -> Self
+            where
+                K: IntoHeaderName,
+                V: TryInto<HeaderValue>,
+                <V as TryInto<HeaderValue>>::Error: Into<Error>,
+                I: IntoIterator<Item = (K, V)>,
+            {
+                let mut err = None;
+                if let Ok(ref mut inner) = self.inner {
+                    let map = inner.trailers.get_or_insert_with(HeaderMap::new);
+                    for (key, value) in trailers.into_iter() {
+                        match value.try_into().map_err(|e| e.into()) {
+                            Ok(v) => {
+                                map.insert(key, v);
+                            }
+                            Err(e) => {
+                                err = Some(e);
+                                break;
+                            }
+                        };
+                    }
+                }
+                if let Some(e) = err {
+                    self.inner = Err(e);
+                }
+                self
+            }
+
             /// Set the body.
             ///
             /// ```
@@ -188,7 +643,33 @@ macro_rules! impl_common_set_methods {
             #[inline]
             pub fn body<V: Into<Vec<u8>>>(mut self, body: V) -> Self {
                 if let Ok(ref mut inner) = self.inner {
-                    inner.body = Body::Bytes(body.into());
+                    inner.body = Body::from(body.into());
+                }
+                self
+            }
+
+            /// Set the typed header, replacing any existing values.
+            ///
+            /// ```
+            /// # use std::time::Duration;
+            /// # use waki::{headers::CacheControl, ResponseBuilder};
+            /// # fn run() {
+            /// # let r = ResponseBuilder::new();
+            /// r.typed_header(CacheControl::new().max_age(Duration::from_secs(60)));
+            /// # }
+            /// ```
+            pub fn typed_header<H: Header>(mut self, header: H) -> Self {
+                let mut err = None;
+                if let Ok(ref mut inner) = self.inner {
+                    match header.encode() {
+                        Ok(value) => {
+                            inner.headers.insert(H::name(), value);
+                        }
+                        Err(e) => err = Some(e),
+                    }
+                }
+                if let Some(e) = err {
+                    self.inner = Err(e);
                 }
                 self
             }
@@ -213,7 +694,7 @@ macro_rules! impl_common_set_methods {
                 if let Ok(ref mut inner) = self.inner {
                     inner.headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
                     match serde_json::to_vec(json) {
-                        Ok(data) => inner.body = Body::Bytes(data),
+                        Ok(data) => inner.body = Body::from(data),
                         Err(e) => err = Some(e.into()),
                     }
                 }
@@ -223,6 +704,146 @@ macro_rules! impl_common_set_methods {
                 self
             }
 
+            /// Set a newline-delimited JSON body, with Content-Type `application/x-ndjson`.
+            ///
+            /// The items are serialized as the body is written, so large exports are never
+            /// buffered in memory.
+            ///
+            /// # Optional
+            ///
+            /// This requires the `json` feature enabled.
+            ///
+            /// ```
+            /// # use std::collections::HashMap;
+            /// # use waki::ResponseBuilder;
+            /// # fn run() {
+            /// # let r = ResponseBuilder::new();
+            /// r.ndjson((0..1000).map(|id| HashMap::from([("id", id)])));
+            /// # }
+            /// ```
+            #[cfg(feature = "json")]
+            pub fn ndjson<I>(self, items: I) -> Self
+            where
+                I: IntoIterator,
+                I::IntoIter: 'static,
+                I::Item: Serialize,
+            {
+                self.json_stream(items, "application/x-ndjson", Format::Lines)
+            }
+
+            /// Set a JSON array body, which is written incrementally as the items are serialized.
+            ///
+            /// # Optional
+            ///
+            /// This requires the `json` feature enabled.
+            ///
+            /// ```
+            /// # use std::collections::HashMap;
+            /// # use waki::ResponseBuilder;
+            /// # fn run() {
+            /// # let r = ResponseBuilder::new();
+            /// r.json_array((0..1000).map(|id| HashMap::from([("id", id)])));
+            /// # }
+            /// ```
+            #[cfg(feature = "json")]
+            pub fn json_array<I>(self, items: I) -> Self
+            where
+                I: IntoIterator,
+                I::IntoIter: 'static,
+                I::Item: Serialize,
+            {
+                self.json_stream(items, "application/json", Format::Array)
+            }
+
+            #[cfg(feature = "json")]
+            fn json_stream<I>(mut self, items: I, content_type: &'static str, format: Format) -> Self
+            where
+                I: IntoIterator,
+                I::IntoIter: 'static,
+                I::Item: Serialize,
+            {
+                if let Ok(ref mut inner) = self.inner {
+                    inner
+                        .headers
+                        .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+                    inner.headers.remove(CONTENT_LENGTH);
+                    inner.body = Body::reader(JsonWriter::new(items.into_iter(), format));
+                }
+                self
+            }
+
+            /// Encode the value with the codec and set it as the body, the Content-Type is set to
+            /// the MIME type of the codec.
+            ///
+            /// ```
+            /// # use std::collections::HashMap;
+            /// # use waki::ResponseBuilder;
+            /// # #[cfg(feature = "msgpack")]
+            /// # fn run() {
+            /// # let r = ResponseBuilder::new();
+            /// r.encode(waki::codec::MsgPack, &HashMap::from([("data", "hello")]));
+            /// # }
+            /// ```
+            pub fn encode<C: Codec, T: Serialize + ?Sized>(mut self, codec: C, value: &T) -> Self {
+                let mut err = None;
+                if let Ok(ref mut inner) = self.inner {
+                    match codec
+                        .encode(value)
+                        .and_then(|data| Ok((HeaderValue::from_str(codec.content_type())?, data)))
+                    {
+                        Ok((content_type, data)) => {
+                            inner.headers.insert(CONTENT_TYPE, content_type);
+                            inner.body = Body::from(data);
+                        }
+                        Err(e) => err = Some(e),
+                    }
+                }
+                if let Some(e) = err {
+                    self.inner = Err(e);
+                }
+                self
+            }
+
+            /// Set a CBOR body.
+            ///
+            /// # Optional
+            ///
+            /// This requires the `cbor` feature enabled.
+            #[cfg(feature = "cbor")]
+            pub fn cbor<T: Serialize + ?Sized>(self, value: &T) -> Self {
+                self.encode(crate::codec::Cbor, value)
+            }
+
+            /// Set a MessagePack body.
+            ///
+            /// # Optional
+            ///
+            /// This requires the `msgpack` feature enabled.
+            #[cfg(feature = "msgpack")]
+            pub fn msgpack<T: Serialize + ?Sized>(self, value: &T) -> Self {
+                self.encode(crate::codec::MsgPack, value)
+            }
+
+            /// Set a XML body.
+            ///
+            /// # Optional
+            ///
+            /// This requires the `xml` feature enabled.
+            #[cfg(feature = "xml")]
+            pub fn xml<T: Serialize + ?Sized>(self, value: &T) -> Self {
+                self.encode(crate::codec::Xml, value)
+            }
+
+            /// Set a TOML body.
+            ///
+            /// # Optional
+            ///
+            /// This requires the `toml` feature enabled.
+            #[cfg(feature = "toml")]
+            pub fn toml<T: Serialize + ?Sized>(self, value: &T) -> Self {
+                self.encode(crate::codec::Toml, value)
+            }
+
             /// Set a form body.
             ///
             /// ```
@@ -246,12 +867,40 @@ macro_rules! impl_common_set_methods {
                     );
                     let mut serializer = form_urlencoded::Serializer::new(String::new());
                     serializer.extend_pairs(form);
-                    inner.body = Body::Bytes(serializer.finish().into())
+                    inner.body = Body::from(Vec::from(serializer.finish()))
                 }
                 self
             }
 
-            /// Set a multipart/form-data body.
+            /// Serialize the value into a form body.
+            ///
+            /// Sequences are serialized as repeated keys, nested structs and maps as `a[b]=c`,
+            /// and `None` values are skipped, see [`RequestBuilder::query_serde`].
+            ///
+            /// ```
+            /// # use std::collections::HashMap;
+            /// # use waki::ResponseBuilder;
+            /// # fn run() {
+            /// # let r = ResponseBuilder::new();
+            /// r.form_serde(&HashMap::from([("tags", vec!["a", "b"])]));
+            /// # }
+            /// ```
+            pub fn form_serde<T: Serialize + ?Sized>(mut self, form: &T) -> Self {
+                match ser::to_pairs(form) {
+                    Ok(pairs) => self.form(pairs),
+                    Err(e) => {
+                        if self.inner.is_ok() {
+                            self.inner = Err(e.into());
+                        }
+                        self
+                    }
+                }
+            }
+
+            /// Set a multipart body, multipart/form-data unless the form has another subtype.
+            ///
+            /// The form is encoded while the body is sent, the data of file and reader parts is
+            /// not buffered. The Content-Length header is set if the sizes of all parts are known.
             ///
             /// # Optional
             ///
@@ -280,14 +929,30 @@ macro_rules! impl_common_set_methods {
             /// ```
             #[cfg(feature = "multipart")]
             pub fn multipart(mut self, form: Form) -> Self {
+                let mut err = None;
                 if let Ok(ref mut inner) = self.inner {
-                    inner.headers.insert(
-                        CONTENT_TYPE,
-                        format!("multipart/form-data; boundary={}", form.boundary())
-                            .parse()
-                            .unwrap(),
-                    );
-                    inner.body = Body::Bytes(form.build());
+                    // the size is unknown if a part has a reader without a length
+                    let content_length = form.content_length();
+                    // the subtype, params and parts of the form are not validated until here
+                    let encoded = form
+                        .content_type()
+                        .parse::<HeaderValue>()
+                        .map_err(Error::new)
+                        .and_then(|v| Ok((v, form.into_reader()?)));
+                    match encoded {
+                        Ok((v, reader)) => {
+                            inner.headers.insert(CONTENT_TYPE, v);
+                            match content_length {
+                                Some(len) => inner.headers.insert(CONTENT_LENGTH, len.into()),
+                                None => inner.headers.remove(CONTENT_LENGTH),
+                            };
+                            inner.body = Body::reader(reader);
+                        }
+                        Err(e) => err = Some(e),
+                    }
+                }
+                if let Some(e) = err {
+                    self.inner = Err(e);
                 }
                 self
             }
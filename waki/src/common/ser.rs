@@ -0,0 +1,605 @@
+//! A serde serializer into key-value pairs, for query strings and form bodies.
+//!
+//! It mirrors the deserializer in [`de`](super::de): sequences are serialized as repeated keys,
+//! nested structs and maps as `a[b]=c`, and `None` values are skipped.
+
+use serde::ser::{self, Impossible, Serialize};
+use std::fmt;
+
+/// Serialize a struct or map into key-value pairs.
+pub(crate) fn to_pairs<T: Serialize + ?Sized>(value: &T) -> Result<Vec<(String, String)>, Error> {
+    let mut pairs = vec![];
+    value.serialize(ValueSerializer {
+        pairs: &mut pairs,
+        key: None,
+        index: None,
+    })?;
+    Ok(pairs)
+}
+
+#[derive(Debug)]
+pub(crate) struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+struct ValueSerializer<'a> {
+    pairs: &'a mut Vec<(String, String)>,
+    // None at the top level, which must be a struct or map
+    key: Option<String>,
+    // the index of the value in a sequence
+    index: Option<usize>,
+}
+
+impl ValueSerializer<'_> {
+    fn push(self, value: String) -> Result<(), Error> {
+        match self.key {
+            Some(key) => {
+                self.pairs.push((key, value));
+                Ok(())
+            }
+            None => Err(Error("expected a struct or map".to_string())),
+        }
+    }
+
+    // The key prefix of nested fields, elements of a sequence are distinguished by their index.
+    fn prefix(&self) -> Option<String> {
+        self.key.as_ref().map(|key| match self.index {
+            Some(i) => format!("{key}[{i}]"),
+            None => key.clone(),
+        })
+    }
+}
+
+fn nested_key(prefix: Option<&str>, field: &str) -> String {
+    match prefix {
+        Some(prefix) => format!("{prefix}[{field}]"),
+        None => field.to_string(),
+    }
+}
+
+macro_rules! serialize_display {
+    ($($method:ident($ty:ty),)*) => ($(
+        fn $method(self, v: $ty) -> Result<(), Error> {
+            self.push(v.to_string())
+        }
+    )*)
+}
+
+impl<'a> ser::Serializer for ValueSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = SeqSerializer<'a>;
+    type SerializeTuple = SeqSerializer<'a>;
+    type SerializeTupleStruct = SeqSerializer<'a>;
+    type SerializeTupleVariant = SeqSerializer<'a>;
+    type SerializeMap = MapSerializer<'a>;
+    type SerializeStruct = MapSerializer<'a>;
+    type SerializeStructVariant = MapSerializer<'a>;
+
+    serialize_display! {
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_i128(i128),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_u128(u128),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), Error> {
+        Err(Error("bytes are not supported".to_string()))
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.push(String::new())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.push(String::new())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.push(variant.to_string())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let key = nested_key(self.prefix().as_deref(), variant);
+        value.serialize(ValueSerializer {
+            pairs: self.pairs,
+            key: Some(key),
+            index: None,
+        })
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<SeqSerializer<'a>, Error> {
+        match self.key {
+            Some(key) => Ok(SeqSerializer {
+                pairs: self.pairs,
+                key,
+                index: 0,
+            }),
+            None => Err(Error("expected a struct or map".to_string())),
+        }
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer<'a>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer<'a>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<SeqSerializer<'a>, Error> {
+        Ok(SeqSerializer {
+            key: nested_key(self.prefix().as_deref(), variant),
+            pairs: self.pairs,
+            index: 0,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer<'a>, Error> {
+        Ok(MapSerializer {
+            prefix: self.prefix(),
+            pairs: self.pairs,
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<MapSerializer<'a>, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<MapSerializer<'a>, Error> {
+        Ok(MapSerializer {
+            prefix: Some(nested_key(self.prefix().as_deref(), variant)),
+            pairs: self.pairs,
+            key: None,
+        })
+    }
+}
+
+struct SeqSerializer<'a> {
+    pairs: &'a mut Vec<(String, String)>,
+    key: String,
+    index: usize,
+}
+
+impl SeqSerializer<'_> {
+    fn serialize<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(ValueSerializer {
+            pairs: self.pairs,
+            key: Some(self.key.clone()),
+            index: Some(self.index),
+        })?;
+        self.index += 1;
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.serialize(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.serialize(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.serialize(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.serialize(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+struct MapSerializer<'a> {
+    pairs: &'a mut Vec<(String, String)>,
+    prefix: Option<String>,
+    // the key passed to `serialize_key`
+    key: Option<String>,
+}
+
+impl MapSerializer<'_> {
+    fn serialize<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+        value.serialize(ValueSerializer {
+            pairs: self.pairs,
+            key: Some(nested_key(self.prefix.as_deref(), key)),
+            index: None,
+        })
+    }
+}
+
+impl ser::SerializeMap for MapSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| Error("serialize_value called before serialize_key".to_string()))?;
+        self.serialize(&key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.serialize(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStructVariant for MapSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.serialize(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+// Map keys must be strings, numbers, booleans or unit variants.
+struct KeySerializer;
+
+fn unsupported_key() -> Error {
+    Error("map keys must be strings or numbers".to_string())
+}
+
+macro_rules! serialize_key_display {
+    ($($method:ident($ty:ty),)*) => ($(
+        fn $method(self, v: $ty) -> Result<String, Error> {
+            Ok(v.to_string())
+        }
+    )*)
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = Error;
+    type SerializeSeq = Impossible<String, Error>;
+    type SerializeTuple = Impossible<String, Error>;
+    type SerializeTupleStruct = Impossible<String, Error>;
+    type SerializeTupleVariant = Impossible<String, Error>;
+    type SerializeMap = Impossible<String, Error>;
+    type SerializeStruct = Impossible<String, Error>;
+    type SerializeStructVariant = Impossible<String, Error>;
+
+    serialize_key_display! {
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_i128(i128),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_u128(u128),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, Error> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_none(self) -> Result<String, Error> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<String, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<String, Error> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, Error> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, Error> {
+        Ok(variant.to_string())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, Error> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(unsupported_key())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::de;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Order {
+        Asc,
+        Desc,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Filter {
+        name: String,
+        min: Option<u32>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Search {
+        q: String,
+        page: Option<u32>,
+        exact: bool,
+        tag: Vec<String>,
+        order: Order,
+        filter: Filter,
+        filters: Vec<Filter>,
+        sort: BTreeMap<u32, Order>,
+    }
+
+    fn search() -> Search {
+        Search {
+            q: "rust wasi".into(),
+            page: None,
+            exact: true,
+            tag: vec!["a".into(), "b".into()],
+            order: Order::Desc,
+            filter: Filter {
+                name: "x".into(),
+                min: Some(3),
+            },
+            filters: vec![
+                Filter {
+                    name: "y".into(),
+                    min: None,
+                },
+                Filter {
+                    name: "z".into(),
+                    min: Some(1),
+                },
+            ],
+            sort: BTreeMap::from([(1, Order::Asc)]),
+        }
+    }
+
+    #[test]
+    fn test_to_pairs() -> Result<(), Error> {
+        let pairs = to_pairs(&search())?;
+        assert_eq!(
+            pairs
+                .iter()
+                .map(|(k, v)| format!("{k}={v}"))
+                .collect::<Vec<_>>(),
+            [
+                "q=rust wasi",
+                "exact=true",
+                "tag=a",
+                "tag=b",
+                "order=desc",
+                "filter[name]=x",
+                "filter[min]=3",
+                "filters[0][name]=y",
+                "filters[1][name]=z",
+                "filters[1][min]=1",
+                "sort[1]=asc",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_roundtrip() -> anyhow::Result<()> {
+        let mut serializer = form_urlencoded::Serializer::new(String::new());
+        serializer.extend_pairs(to_pairs(&search())?);
+        let query = serializer.finish();
+        assert_eq!(de::from_urlencoded::<Search>(query.as_bytes())?, search());
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(to_pairs(&"text").is_err());
+        assert!(to_pairs(&["a", "b"]).is_err());
+        assert!(to_pairs(&BTreeMap::from([((1, 2), "a")])).is_err());
+    }
+}
@@ -0,0 +1,218 @@
+use crate::{
+    headers::{
+        ETag, EntityTag, IfMatch, IfModifiedSince, IfNoneMatch, IfUnmodifiedSince, LastModified,
+    },
+    Method, Request, Response,
+};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+impl Request {
+    /// Evaluate the conditional headers against the current representation of the resource,
+    /// returning the response to answer with if a precondition fails.
+    ///
+    /// `If-Match`, `If-Unmodified-Since`, `If-None-Match` and `If-Modified-Since` are evaluated
+    /// in the order and with the precedence defined by RFC 9110: a failed `If-None-Match` or
+    /// `If-Modified-Since` results in `304 Not Modified` for GET and HEAD requests, any other
+    /// failed precondition in `412 Precondition Failed`.
+    ///
+    /// The representation is considered to exist if either validator is given, so for a missing
+    /// resource `If-Match: *` fails and `If-None-Match: *` passes.
+    ///
+    /// ```
+    /// # use waki::{headers::{ETag, EntityTag}, ErrorCode, Request, Response};
+    /// fn handler(req: Request) -> Result<Response, ErrorCode> {
+    ///     let body = b"Hello, WASI!";
+    ///     let etag = EntityTag::from_content(body);
+    ///     if let Some(resp) = req.preconditions(Some(&etag), None) {
+    ///         return Ok(resp);
+    ///     }
+    ///     Response::builder().typed_header(ETag(etag)).body(body).build()
+    /// }
+    /// ```
+    pub fn preconditions(
+        &self,
+        etag: Option<&EntityTag>,
+        last_modified: Option<SystemTime>,
+    ) -> Option<Response> {
+        let exists = etag.is_some() || last_modified.is_some();
+        // HTTP dates have a precision of seconds
+        let last_modified = last_modified.map(truncate);
+
+        match self.typed_header::<IfMatch>() {
+            Some(IfMatch::Any) if !exists => return Some(status(412)),
+            Some(IfMatch::Tags(tags))
+                if !etag.is_some_and(|etag| tags.iter().any(|tag| tag.strong_eq(etag))) =>
+            {
+                return Some(status(412))
+            }
+            Some(_) => {}
+            None => {
+                if let (Some(IfUnmodifiedSince(since)), Some(last_modified)) =
+                    (self.typed_header(), last_modified)
+                {
+                    if last_modified > since {
+                        return Some(status(412));
+                    }
+                }
+            }
+        }
+
+        let safe = matches!(self.method(), Method::Get | Method::Head);
+        let modified = match self.typed_header::<IfNoneMatch>() {
+            Some(IfNoneMatch::Any) => !exists,
+            Some(IfNoneMatch::Tags(tags)) => {
+                !etag.is_some_and(|etag| tags.iter().any(|tag| tag.weak_eq(etag)))
+            }
+            None if safe => match (self.typed_header(), last_modified) {
+                (Some(IfModifiedSince(since)), Some(last_modified)) => last_modified > since,
+                _ => true,
+            },
+            None => true,
+        };
+        if modified {
+            None
+        } else if safe {
+            let mut builder = Response::builder().status_code(304);
+            if let Some(etag) = etag {
+                builder = builder.typed_header(ETag(etag.clone()));
+            }
+            if let Some(last_modified) = last_modified {
+                builder = builder.typed_header(LastModified(last_modified));
+            }
+            Some(builder.build().unwrap())
+        } else {
+            Some(status(412))
+        }
+    }
+}
+
+fn status(status_code: u16) -> Response {
+    Response::builder()
+        .status_code(status_code)
+        .build()
+        .unwrap()
+}
+
+fn truncate(time: SystemTime) -> SystemTime {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => UNIX_EPOCH + Duration::from_secs(d.as_secs()),
+        Err(_) => time,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::{HeaderMap, HeaderValue};
+
+    const DATE: &str = "Sun, 06 Nov 1994 08:49:37 GMT";
+
+    fn time(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(784111777 + secs)
+    }
+
+    #[track_caller]
+    fn evaluate(
+        method: Method,
+        headers: &[(&'static str, &'static str)],
+        etag: Option<&str>,
+        last_modified: Option<SystemTime>,
+    ) -> Option<u16> {
+        let mut req = Request::new(method, Default::default());
+        req.headers = headers
+            .iter()
+            .map(|(k, v)| (k.parse().unwrap(), HeaderValue::from_static(v)))
+            .collect::<HeaderMap>();
+        let etag = etag.map(|etag| etag.parse::<EntityTag>().unwrap());
+        req.preconditions(etag.as_ref(), last_modified)
+            .map(|resp| resp.status_code())
+    }
+
+    #[test]
+    fn test_if_match() {
+        let check = |value, etag| evaluate(Method::Put, &[("if-match", value)], etag, None);
+        assert_eq!(check("\"a\", \"b\"", Some("\"b\"")), None);
+        assert_eq!(check("\"a\"", Some("\"b\"")), Some(412));
+        // strong comparison
+        assert_eq!(check("W/\"a\"", Some("W/\"a\"")), Some(412));
+        assert_eq!(check("*", Some("\"a\"")), None);
+        assert_eq!(check("*", None), Some(412));
+        assert_eq!(check("\"a\"", None), Some(412));
+    }
+
+    #[test]
+    fn test_if_unmodified_since() {
+        let check = |headers, last_modified| evaluate(Method::Put, headers, None, last_modified);
+        assert_eq!(check(&[("if-unmodified-since", DATE)], Some(time(0))), None);
+        assert_eq!(
+            check(&[("if-unmodified-since", DATE)], Some(time(1))),
+            Some(412)
+        );
+        // sub-second precision is ignored
+        assert_eq!(
+            check(
+                &[("if-unmodified-since", DATE)],
+                Some(time(0) + Duration::from_millis(500))
+            ),
+            None
+        );
+        assert_eq!(check(&[("if-unmodified-since", DATE)], None), None);
+        assert_eq!(
+            check(&[("if-unmodified-since", "invalid")], Some(time(1))),
+            None
+        );
+        // ignored when If-Match is present
+        assert_eq!(
+            evaluate(
+                Method::Put,
+                &[("if-match", "*"), ("if-unmodified-since", DATE)],
+                Some("\"a\""),
+                Some(time(1))
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let check = |method, value, etag| evaluate(method, &[("if-none-match", value)], etag, None);
+        assert_eq!(check(Method::Get, "\"a\"", Some("\"b\"")), None);
+        assert_eq!(check(Method::Get, "\"a\", \"b\"", Some("\"b\"")), Some(304));
+        // weak comparison
+        assert_eq!(check(Method::Head, "W/\"b\"", Some("\"b\"")), Some(304));
+        assert_eq!(check(Method::Post, "\"b\"", Some("\"b\"")), Some(412));
+        assert_eq!(check(Method::Put, "*", Some("\"b\"")), Some(412));
+        assert_eq!(check(Method::Put, "*", None), None);
+
+        let mut req = Request::new(Method::Get, Default::default());
+        req.headers
+            .insert("if-none-match", HeaderValue::from_static("\"a\""));
+        let resp = req
+            .preconditions(Some(&EntityTag::strong("a").unwrap()), Some(time(0)))
+            .unwrap();
+        assert_eq!(resp.header("etag").unwrap(), "\"a\"");
+        assert_eq!(resp.header("last-modified").unwrap(), DATE);
+    }
+
+    #[test]
+    fn test_if_modified_since() {
+        let check = |method, headers, last_modified| {
+            evaluate(method, headers, Some("\"a\""), last_modified)
+        };
+        let headers = &[("if-modified-since", DATE)];
+        assert_eq!(check(Method::Get, headers, Some(time(0))), Some(304));
+        assert_eq!(check(Method::Get, headers, Some(time(1))), None);
+        assert_eq!(check(Method::Get, headers, None), None);
+        // only for GET and HEAD
+        assert_eq!(check(Method::Post, headers, Some(time(0))), None);
+        // ignored when If-None-Match is present
+        assert_eq!(
+            check(
+                Method::Get,
+                &[("if-none-match", "\"b\""), ("if-modified-since", DATE)],
+                Some(time(0))
+            ),
+            None
+        );
+    }
+}
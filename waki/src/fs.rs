@@ -0,0 +1,771 @@
+//! Static files served from the filesystem, e.g. WASI preopened directories.
+//!
+//! ```
+//! # use waki::{fs::ServeDir, handler, ErrorCode, Request, Response};
+//! #[handler]
+//! fn assets(req: Request) -> Result<Response, ErrorCode> {
+//!     ServeDir::new("assets").strip_prefix("/static").serve(&req)
+//! }
+//! ```
+//!
+//! Files are streamed, with the MIME type guessed from the extension. The `ETag` and
+//! `Last-Modified` headers are set from the file metadata, so conditional and range requests
+//! are answered, see [`Request::preconditions`] and [`ResponseBuilder::ranged_body`].
+//!
+//! Directories can also be embedded into the component at compile time with
+//! [`embed_dir!`](crate::embed_dir), for hosts that give components no filesystem.
+//!
+//! [`ResponseBuilder::ranged_body`]: crate::ResponseBuilder::ranged_body
+
+use crate::{
+    header::{HeaderValue, ALLOW, CONTENT_ENCODING, CONTENT_TYPE, LOCATION, VARY},
+    headers::{ETag, EntityTag, LastModified},
+    ErrorCode, Method, Request, Response,
+};
+use mime::Mime;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use std::fs::{self, File};
+use std::io::{self, Cursor, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+// characters that must be escaped in a path segment of a URL
+const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Serve the files of a directory, mapping the request path to a file under the root.
+///
+/// Paths containing `..` segments are rejected, so files outside the root cannot be reached.
+#[derive(Debug, Clone)]
+pub struct ServeDir {
+    root: PathBuf,
+    prefix: Option<String>,
+    index_files: Vec<String>,
+    listing: bool,
+}
+
+impl ServeDir {
+    /// Serve the directory, with `index.html` as index file and directory listings disabled.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self {
+            root: root.into(),
+            prefix: None,
+            index_files: vec!["index.html".into()],
+            listing: false,
+        }
+    }
+
+    /// Remove the prefix from the request path before mapping it to a file, requests with
+    /// paths outside the prefix are answered with 404.
+    pub fn strip_prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Set the files served for a directory, tried in order. An empty list disables them.
+    pub fn index_files<S, I>(mut self, names: I) -> Self
+    where
+        S: Into<String>,
+        I: IntoIterator<Item = S>,
+    {
+        self.index_files = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Enable HTML listings for directories without an index file.
+    pub fn directory_listing(mut self, enabled: bool) -> Self {
+        self.listing = enabled;
+        self
+    }
+
+    /// Answer the request with the file its path maps to.
+    ///
+    /// Requests for a directory without a trailing slash are redirected, so that relative links
+    /// resolve correctly. Only GET and HEAD are allowed.
+    pub fn serve(&self, req: &Request) -> Result<Response, ErrorCode> {
+        if !matches!(req.method(), Method::Get | Method::Head) {
+            return method_not_allowed();
+        }
+        let path = req.path();
+        let Some(segments) = segments(self.prefix.as_deref(), path) else {
+            return status(404);
+        };
+        let mut full = self.root.clone();
+        full.extend(&segments);
+
+        let metadata = match fs::metadata(&full) {
+            Ok(metadata) => metadata,
+            // a file used as a directory, e.g. `/style.css/x`
+            Err(_) if full.ancestors().skip(1).any(Path::is_file) => return status(404),
+            Err(e) => return io_error(e),
+        };
+        if !metadata.is_dir() {
+            return serve_file(req, &full, None);
+        }
+        if !path.ends_with('/') {
+            return redirect_to_dir(req);
+        }
+        for name in &self.index_files {
+            let index = full.join(name);
+            if index.is_file() {
+                return serve_file(req, &index, None);
+            }
+        }
+        if self.listing {
+            return listing(&full, path, !segments.is_empty());
+        }
+        status(404)
+    }
+}
+
+/// Serve a single file.
+#[derive(Debug, Clone)]
+pub struct ServeFile {
+    path: PathBuf,
+    mime: Option<Mime>,
+}
+
+impl ServeFile {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            mime: None,
+        }
+    }
+
+    /// Set the MIME type instead of guessing it from the extension.
+    pub fn mime(mut self, mime: Mime) -> Self {
+        self.mime = Some(mime);
+        self
+    }
+
+    /// Answer the request with the file, only GET and HEAD are allowed.
+    pub fn serve(&self, req: &Request) -> Result<Response, ErrorCode> {
+        if !matches!(req.method(), Method::Get | Method::Head) {
+            return method_not_allowed();
+        }
+        serve_file(req, &self.path, self.mime.clone())
+    }
+}
+
+/// A directory embedded into the component with [`embed_dir!`].
+///
+/// It is served like [`ServeDir`], except that directory listings are not supported.
+///
+/// [`embed_dir!`]: crate::embed_dir
+#[derive(Debug, Clone)]
+pub struct EmbeddedDir {
+    files: &'static [EmbeddedFile],
+    prefix: Option<String>,
+    index_files: Vec<String>,
+}
+
+impl EmbeddedDir {
+    #[doc(hidden)]
+    pub fn new(files: &'static [EmbeddedFile]) -> Self {
+        Self {
+            files,
+            prefix: None,
+            index_files: vec!["index.html".into()],
+        }
+    }
+
+    /// Remove the prefix from the request path before mapping it to a file, requests with
+    /// paths outside the prefix are answered with 404.
+    pub fn strip_prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Set the files served for a directory, tried in order. An empty list disables them.
+    pub fn index_files<S, I>(mut self, names: I) -> Self
+    where
+        S: Into<String>,
+        I: IntoIterator<Item = S>,
+    {
+        self.index_files = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Get a file by its path relative to the directory, e.g. `css/style.css`.
+    pub fn get(&self, path: &str) -> Option<&'static EmbeddedFile> {
+        let files = self.files;
+        files
+            .binary_search_by(|file| file.path.cmp(path))
+            .ok()
+            .map(|i| &files[i])
+    }
+
+    /// Iterate over the embedded files, ordered by path.
+    pub fn files(&self) -> impl Iterator<Item = &'static EmbeddedFile> {
+        self.files.iter()
+    }
+
+    fn is_dir(&self, path: &str) -> bool {
+        path.is_empty()
+            || self.files.iter().any(|file| {
+                file.path
+                    .strip_prefix(path)
+                    .is_some_and(|rest| rest.starts_with('/'))
+            })
+    }
+
+    /// Answer the request with the file its path maps to.
+    ///
+    /// Requests for a directory without a trailing slash are redirected, so that relative links
+    /// resolve correctly. Only GET and HEAD are allowed.
+    pub fn serve(&self, req: &Request) -> Result<Response, ErrorCode> {
+        if !matches!(req.method(), Method::Get | Method::Head) {
+            return method_not_allowed();
+        }
+        let path = req.path();
+        let Some(segments) = segments(self.prefix.as_deref(), path) else {
+            return status(404);
+        };
+        let relative = segments.join("/");
+        if let Some(file) = self.get(&relative) {
+            return file.respond(req);
+        }
+        if !self.is_dir(&relative) {
+            return status(404);
+        }
+        if !path.ends_with('/') {
+            return redirect_to_dir(req);
+        }
+        for name in &self.index_files {
+            let index = match relative.as_str() {
+                "" => self.get(name),
+                dir => self.get(&format!("{}/{}", dir, name)),
+            };
+            if let Some(file) = index {
+                return file.respond(req);
+            }
+        }
+        status(404)
+    }
+}
+
+/// A file embedded with [`embed_dir!`], with its MIME type and entity tag computed at compile
+/// time.
+///
+/// [`embed_dir!`]: crate::embed_dir
+#[derive(Debug)]
+pub struct EmbeddedFile {
+    path: &'static str,
+    mime: &'static str,
+    etag: &'static str,
+    content: &'static [u8],
+    variants: &'static [EmbeddedVariant],
+}
+
+/// A pre-compressed variant of an [`EmbeddedFile`].
+#[derive(Debug)]
+pub struct EmbeddedVariant {
+    encoding: &'static str,
+    etag: &'static str,
+    content: &'static [u8],
+}
+
+impl EmbeddedVariant {
+    #[doc(hidden)]
+    pub const fn new(encoding: &'static str, etag: &'static str, content: &'static [u8]) -> Self {
+        Self {
+            encoding,
+            etag,
+            content,
+        }
+    }
+}
+
+impl EmbeddedFile {
+    #[doc(hidden)]
+    pub const fn new(
+        path: &'static str,
+        mime: &'static str,
+        etag: &'static str,
+        content: &'static [u8],
+        variants: &'static [EmbeddedVariant],
+    ) -> Self {
+        Self {
+            path,
+            mime,
+            etag,
+            content,
+            variants,
+        }
+    }
+
+    /// Get the path relative to the embedded directory.
+    #[inline]
+    pub fn path(&self) -> &'static str {
+        self.path
+    }
+
+    #[inline]
+    pub fn mime(&self) -> &'static str {
+        self.mime
+    }
+
+    #[inline]
+    pub fn content(&self) -> &'static [u8] {
+        self.content
+    }
+
+    /// Get the content codings of the pre-compressed variants, e.g. `gzip` or `br`.
+    pub fn encodings(&self) -> impl Iterator<Item = &'static str> {
+        self.variants.iter().map(|variant| variant.encoding)
+    }
+
+    /// Answer the request with the file, only GET and HEAD are allowed.
+    ///
+    /// A pre-compressed variant is sent if the `Accept-Encoding` header of the request prefers it.
+    pub fn serve(&self, req: &Request) -> Result<Response, ErrorCode> {
+        if !matches!(req.method(), Method::Get | Method::Head) {
+            return method_not_allowed();
+        }
+        self.respond(req)
+    }
+
+    fn respond(&self, req: &Request) -> Result<Response, ErrorCode> {
+        let (encoding, etag, content) = match self.variants {
+            [] => ("identity", self.etag, self.content),
+            variants => {
+                let mut available = self.encodings().collect::<Vec<_>>();
+                available.push("identity");
+                let encoding = match req.negotiate_encoding(&available) {
+                    Ok(encoding) => encoding,
+                    Err(e) => return Ok(vary_encoding(e.into())),
+                };
+                match variants.iter().find(|v| v.encoding == encoding) {
+                    Some(variant) => (encoding, variant.etag, variant.content),
+                    None => (encoding, self.etag, self.content),
+                }
+            }
+        };
+        // the tags are computed by `embed_dir!`, so they are valid
+        let etag = EntityTag::strong(etag).ok();
+        if let Some(resp) = req.preconditions(etag.as_ref(), None) {
+            return Ok(self.vary(resp));
+        }
+
+        let mut builder = Response::builder().header(CONTENT_TYPE, self.mime);
+        if let Some(etag) = etag {
+            builder = builder.typed_header(ETag(etag));
+        }
+        if encoding != "identity" {
+            builder = builder.header(CONTENT_ENCODING, encoding);
+        }
+        let len = content.len() as u64;
+        let resp = builder
+            .ranged_body(req, Cursor::new(content), len)
+            .build()?;
+        Ok(self.vary(resp))
+    }
+
+    fn vary(&self, resp: Response) -> Response {
+        match self.variants {
+            [] => resp,
+            _ => vary_encoding(resp),
+        }
+    }
+}
+
+/// Mark the response as depending on the `Accept-Encoding` header.
+fn vary_encoding(mut resp: Response) -> Response {
+    resp.headers
+        .insert(VARY, HeaderValue::from_static("accept-encoding"));
+    resp
+}
+
+/// Split the request path, without the prefix, into decoded segments. `None` if the path is
+/// outside the prefix or could escape the root.
+fn segments(prefix: Option<&str>, path: &str) -> Option<Vec<String>> {
+    let path = match prefix {
+        Some(prefix) => match path.strip_prefix(prefix.trim_end_matches('/')) {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => rest,
+            _ => return None,
+        },
+        None => path,
+    };
+    let mut segments = vec![];
+    for segment in path.split('/') {
+        let segment = percent_decode_str(segment).decode_utf8().ok()?;
+        match segment.as_ref() {
+            "" | "." => {}
+            ".." => return None,
+            s if s.contains(['/', '\\', '\0']) => return None,
+            _ => segments.push(segment.into_owned()),
+        }
+    }
+    Some(segments)
+}
+
+fn serve_file(req: &Request, path: &Path, mime: Option<Mime>) -> Result<Response, ErrorCode> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => return io_error(e),
+    };
+    let metadata = match file.metadata() {
+        Ok(metadata) if metadata.is_dir() => return status(404),
+        Ok(metadata) => metadata,
+        Err(e) => return io_error(e),
+    };
+    let len = metadata.len();
+    let modified = metadata.modified().ok();
+    // a weak validator, as the file could change within the precision of the timestamp
+    let etag = modified
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .and_then(|mtime| {
+            EntityTag::weak(format!(
+                "{:x}-{:x}.{:x}",
+                len,
+                mtime.as_secs(),
+                mtime.subsec_nanos()
+            ))
+            .ok()
+        });
+    if let Some(resp) = req.preconditions(etag.as_ref(), modified) {
+        return Ok(resp);
+    }
+
+    let mime = mime.unwrap_or_else(|| mime_guess::from_path(path).first_or_octet_stream());
+    let mut builder = Response::builder().header(CONTENT_TYPE, mime.as_ref());
+    if let Some(etag) = etag {
+        builder = builder.typed_header(ETag(etag));
+    }
+    if let Some(modified) = modified {
+        builder = builder.typed_header(LastModified(modified));
+    }
+    builder.ranged_body(req, file, len).build()
+}
+
+fn listing(dir: &Path, path: &str, parent: bool) -> Result<Response, ErrorCode> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => return io_error(e),
+    };
+    let mut names = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name().into_string().ok()?;
+            match entry.file_type().ok()?.is_dir() {
+                true => Some(format!("{}/", name)),
+                false => Some(name),
+            }
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+    if parent {
+        names.insert(0, "../".into());
+    }
+
+    let title = escape(&percent_decode_str(path).decode_utf8_lossy());
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Index of {title}</title></head>\n<body>\n<h1>Index of {title}</h1>\n<ul>\n"
+    );
+    for name in names {
+        let (base, slash) = match name.strip_suffix('/') {
+            Some(base) => (base, "/"),
+            None => (name.as_str(), ""),
+        };
+        let href = match base {
+            ".." => "..".to_string(),
+            _ => utf8_percent_encode(base, SEGMENT).to_string(),
+        };
+        html += &format!("<li><a href=\"{href}{slash}\">{}</a></li>\n", escape(&name));
+    }
+    html += "</ul>\n</body>\n</html>\n";
+    Response::builder()
+        .header(CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(html)
+        .build()
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn io_error(e: io::Error) -> Result<Response, ErrorCode> {
+    match e.kind() {
+        ErrorKind::NotFound => status(404),
+        ErrorKind::PermissionDenied => status(403),
+        _ => Err(ErrorCode::InternalError(Some(e.to_string()))),
+    }
+}
+
+fn status(status_code: u16) -> Result<Response, ErrorCode> {
+    Response::builder().status_code(status_code).build()
+}
+
+fn redirect_to_dir(req: &Request) -> Result<Response, ErrorCode> {
+    // relative to the requested path, as a path such as `//docs` is a protocol-relative URL
+    let last = req.path().rsplit('/').next().unwrap_or_default();
+    let mut location = format!("./{}/", last);
+    if let Some(query) = req.uri.path_and_query.as_ref().and_then(|p| p.query()) {
+        location.push('?');
+        location.push_str(query);
+    }
+    Response::builder()
+        .status_code(301)
+        .header(LOCATION, location)
+        .build()
+}
+
+fn method_not_allowed() -> Result<Response, ErrorCode> {
+    Response::builder()
+        .status_code(405)
+        .header(ALLOW, "GET, HEAD")
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::HeaderValue;
+
+    const ROOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/all/fixtures/static");
+
+    fn request(method: Method, path: &str, headers: &[(&'static str, &str)]) -> Request {
+        let mut uri = http::uri::Parts::default();
+        uri.path_and_query = Some(path.parse().unwrap());
+        let mut req = Request::new(method, uri);
+        for (k, v) in headers {
+            req.headers.insert(*k, HeaderValue::from_str(v).unwrap());
+        }
+        req
+    }
+
+    fn get(dir: &ServeDir, path: &str) -> Response {
+        dir.serve(&request(Method::Get, path, &[])).unwrap()
+    }
+
+    #[test]
+    fn test_serve_dir() {
+        let dir = ServeDir::new(ROOT);
+
+        let resp = get(&dir, "/style.css");
+        assert_eq!(resp.status_code(), 200);
+        assert_eq!(resp.header(CONTENT_TYPE).unwrap(), "text/css");
+        assert_eq!(resp.header("accept-ranges").unwrap(), "bytes");
+        assert!(resp
+            .header("etag")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("W/\""));
+        assert!(resp.header("last-modified").is_some());
+        assert_eq!(resp.body().unwrap(), b"body { color: red; }\n");
+
+        let resp = get(&dir, "/");
+        assert_eq!(resp.header(CONTENT_TYPE).unwrap(), "text/html");
+        assert_eq!(resp.body().unwrap(), b"<!DOCTYPE html>\n<h1>Hello</h1>\n");
+
+        assert_eq!(get(&dir, "/%64ocs/guide.txt").status_code(), 200);
+        assert_eq!(get(&dir, "/missing.txt").status_code(), 404);
+        assert_eq!(get(&dir, "/style.css/x").status_code(), 404);
+        assert_eq!(get(&dir, "/docs/").status_code(), 404);
+
+        let resp = get(&dir, "/docs");
+        assert_eq!(resp.status_code(), 301);
+        assert_eq!(resp.header(LOCATION).unwrap(), "./docs/");
+        let resp = get(&dir, "//docs?a=b");
+        assert_eq!(resp.status_code(), 301);
+        assert_eq!(resp.header(LOCATION).unwrap(), "./docs/?a=b");
+
+        let resp = dir
+            .serve(&request(Method::Post, "/style.css", &[]))
+            .unwrap();
+        assert_eq!(resp.status_code(), 405);
+        assert_eq!(resp.header(ALLOW).unwrap(), "GET, HEAD");
+    }
+
+    #[test]
+    fn test_traversal() {
+        // the fixtures directory contains file.txt
+        let dir = ServeDir::new(ROOT);
+        for path in [
+            "/../file.txt",
+            "/docs/../../file.txt",
+            "/%2e%2e/file.txt",
+            "/docs%2f..%2f..%2ffile.txt",
+            "/..%5cfile.txt",
+            "/%ff",
+        ] {
+            assert_eq!(get(&dir, path).status_code(), 404, "{path}");
+        }
+    }
+
+    #[test]
+    fn test_prefix_and_index() {
+        let dir = ServeDir::new(ROOT)
+            .strip_prefix("/static/")
+            .index_files(["guide.txt"]);
+        assert_eq!(get(&dir, "/static/style.css").status_code(), 200);
+        assert_eq!(get(&dir, "/style.css").status_code(), 404);
+        assert_eq!(get(&dir, "/staticstyle.css").status_code(), 404);
+        assert_eq!(get(&dir, "/static/").status_code(), 404);
+        assert_eq!(get(&dir, "/static/docs/").body().unwrap(), b"guide\n");
+    }
+
+    #[test]
+    fn test_listing() {
+        let dir = ServeDir::new(ROOT)
+            .index_files(Vec::<String>::new())
+            .directory_listing(true);
+
+        let resp = get(&dir, "/");
+        assert_eq!(
+            resp.header(CONTENT_TYPE).unwrap(),
+            "text/html; charset=utf-8"
+        );
+        let html = String::from_utf8(resp.body().unwrap()).unwrap();
+        assert!(html.contains("<title>Index of /</title>"));
+        assert!(html.contains(
+            "<li><a href=\"docs/\">docs/</a></li>\n<li><a href=\"index.html\">index.html</a></li>"
+        ));
+        assert!(!html.contains("../"));
+
+        let html = String::from_utf8(get(&dir, "/docs/").body().unwrap()).unwrap();
+        assert!(html.contains(
+            "<li><a href=\"../\">../</a></li>\n<li><a href=\"guide.txt\">guide.txt</a></li>"
+        ));
+    }
+
+    #[test]
+    fn test_conditional_and_range() {
+        let dir = ServeDir::new(ROOT);
+        let resp = get(&dir, "/style.css");
+        let etag = resp.header("etag").unwrap().to_str().unwrap().to_string();
+
+        let resp = dir
+            .serve(&request(
+                Method::Get,
+                "/style.css",
+                &[("if-none-match", &etag)],
+            ))
+            .unwrap();
+        assert_eq!(resp.status_code(), 304);
+
+        let resp = dir
+            .serve(&request(
+                Method::Get,
+                "/style.css",
+                &[("range", "bytes=0-3")],
+            ))
+            .unwrap();
+        assert_eq!(resp.status_code(), 206);
+        assert_eq!(resp.body().unwrap(), b"body");
+    }
+
+    #[test]
+    fn test_serve_file() {
+        let file = ServeFile::new(format!("{ROOT}/docs/guide.txt"));
+        let resp = file.serve(&request(Method::Get, "/any", &[])).unwrap();
+        assert_eq!(resp.header(CONTENT_TYPE).unwrap(), "text/plain");
+        assert_eq!(resp.body().unwrap(), b"guide\n");
+
+        let file = ServeFile::new(format!("{ROOT}/docs/guide.txt")).mime(mime::TEXT_CSV);
+        let resp = file.serve(&request(Method::Head, "/any", &[])).unwrap();
+        assert_eq!(resp.header(CONTENT_TYPE).unwrap(), "text/csv");
+
+        let file = ServeFile::new(format!("{ROOT}/docs"));
+        assert_eq!(
+            file.serve(&request(Method::Get, "/", &[]))
+                .unwrap()
+                .status_code(),
+            404
+        );
+    }
+
+    static FILES: &[EmbeddedFile] = &[
+        EmbeddedFile::new("docs/guide.txt", "text/plain", "6-1", b"guide\n", &[]),
+        EmbeddedFile::new("index.html", "text/html", "7-1", b"<p></p>", &[]),
+        EmbeddedFile::new(
+            "style.css",
+            "text/css",
+            "4-1",
+            b"body",
+            &[
+                EmbeddedVariant::new("br", "2-2", b"br"),
+                EmbeddedVariant::new("gzip", "4-3", b"gzip"),
+            ],
+        ),
+    ];
+
+    #[test]
+    fn test_embedded_dir() {
+        let dir = EmbeddedDir::new(FILES).strip_prefix("/static");
+        let get = |path| dir.serve(&request(Method::Get, path, &[])).unwrap();
+
+        let resp = get("/static/docs/guide.txt");
+        assert_eq!(resp.header(CONTENT_TYPE).unwrap(), "text/plain");
+        assert_eq!(resp.header("etag").unwrap(), "\"6-1\"");
+        assert!(resp.header(VARY).is_none());
+        assert_eq!(resp.body().unwrap(), b"guide\n");
+
+        assert_eq!(get("/static/").body().unwrap(), b"<p></p>");
+        let resp = get("/static//docs");
+        assert_eq!(resp.status_code(), 301);
+        assert_eq!(resp.header(LOCATION).unwrap(), "./docs/");
+        assert_eq!(get("/static/docs/").status_code(), 404);
+        assert_eq!(get("/static/do").status_code(), 404);
+        assert_eq!(get("/static/../static/style.css").status_code(), 404);
+        assert_eq!(get("/style.css").status_code(), 404);
+
+        let resp = dir
+            .serve(&request(
+                Method::Get,
+                "/static/index.html",
+                &[("if-none-match", "\"7-1\"")],
+            ))
+            .unwrap();
+        assert_eq!(resp.status_code(), 304);
+    }
+
+    #[test]
+    fn test_embedded_encoding() {
+        let file = EmbeddedDir::new(FILES).get("style.css").unwrap();
+        assert_eq!(file.encodings().collect::<Vec<_>>(), ["br", "gzip"]);
+        let serve = |accept| {
+            file.serve(&request(Method::Get, "/", &[("accept-encoding", accept)]))
+                .unwrap()
+        };
+
+        let resp = serve("gzip, br;q=0.5");
+        assert_eq!(resp.header(CONTENT_ENCODING).unwrap(), "gzip");
+        assert_eq!(resp.header(VARY).unwrap(), "accept-encoding");
+        assert_eq!(resp.header("etag").unwrap(), "\"4-3\"");
+        assert_eq!(resp.header(CONTENT_TYPE).unwrap(), "text/css");
+        assert_eq!(resp.body().unwrap(), b"gzip");
+
+        let resp = serve("deflate");
+        assert!(resp.header(CONTENT_ENCODING).is_none());
+        assert_eq!(resp.header(VARY).unwrap(), "accept-encoding");
+        assert_eq!(resp.body().unwrap(), b"body");
+
+        let resp = serve("deflate, identity;q=0");
+        assert_eq!(resp.status_code(), 406);
+        assert_eq!(resp.header(VARY).unwrap(), "accept-encoding");
+    }
+}
@@ -0,0 +1,505 @@
+//! gRPC and gRPC-Web client.
+//!
+//! Messages are encoded with [`prost`], so types generated by `prost-build` can be used directly.
+//!
+//! ```
+//! # use waki::grpc::{Channel, Status};
+//! # fn run() -> Result<(), Status> {
+//! // e.g. a message generated by prost-build
+//! let request = String::from("world");
+//!
+//! let channel = Channel::new("http://localhost:50051").header("authorization", "Bearer token");
+//! let reply: String = channel.unary("/helloworld.Greeter/SayHello", &request)?;
+//!
+//! for reply in channel.server_streaming::<_, String>("/helloworld.Greeter/SayHellos", &request)? {
+//!     println!("{}", reply?);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    header::{HeaderMap, HeaderValue, IntoHeaderName, ACCEPT, CONTENT_TYPE, TE},
+    Client, Response,
+};
+
+use anyhow::Error;
+use percent_encoding::percent_decode_str;
+use prost::Message;
+use std::fmt;
+use std::io::{self, Read};
+use std::marker::PhantomData;
+use std::time::Duration;
+
+const GRPC_CONTENT_TYPE: &str = "application/grpc+proto";
+const GRPC_WEB_CONTENT_TYPE: &str = "application/grpc-web+proto";
+const GRPC_STATUS: &str = "grpc-status";
+const GRPC_MESSAGE: &str = "grpc-message";
+
+// the first byte of each length-prefixed message
+const FLAG_COMPRESSED: u8 = 0x01;
+const FLAG_TRAILERS: u8 = 0x80;
+
+const DEFAULT_MAX_DECODING_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
+/// gRPC status codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Code {
+    Ok = 0,
+    Cancelled = 1,
+    Unknown = 2,
+    InvalidArgument = 3,
+    DeadlineExceeded = 4,
+    NotFound = 5,
+    AlreadyExists = 6,
+    PermissionDenied = 7,
+    ResourceExhausted = 8,
+    FailedPrecondition = 9,
+    Aborted = 10,
+    OutOfRange = 11,
+    Unimplemented = 12,
+    Internal = 13,
+    Unavailable = 14,
+    DataLoss = 15,
+    Unauthenticated = 16,
+}
+
+impl From<i32> for Code {
+    fn from(code: i32) -> Self {
+        match code {
+            0 => Code::Ok,
+            1 => Code::Cancelled,
+            3 => Code::InvalidArgument,
+            4 => Code::DeadlineExceeded,
+            5 => Code::NotFound,
+            6 => Code::AlreadyExists,
+            7 => Code::PermissionDenied,
+            8 => Code::ResourceExhausted,
+            9 => Code::FailedPrecondition,
+            10 => Code::Aborted,
+            11 => Code::OutOfRange,
+            12 => Code::Unimplemented,
+            13 => Code::Internal,
+            14 => Code::Unavailable,
+            15 => Code::DataLoss,
+            16 => Code::Unauthenticated,
+            _ => Code::Unknown,
+        }
+    }
+}
+
+impl Code {
+    // ref: https://github.com/grpc/grpc/blob/master/doc/http-grpc-status-mapping.md
+    fn from_http_status(status_code: u16) -> Self {
+        match status_code {
+            400 => Code::Internal,
+            401 => Code::Unauthenticated,
+            403 => Code::PermissionDenied,
+            404 => Code::Unimplemented,
+            429 | 502 | 503 | 504 => Code::Unavailable,
+            _ => Code::Unknown,
+        }
+    }
+}
+
+/// The status of a gRPC call, returned as the error of a failed call.
+///
+/// Transport failures are reported with the [`Code::Unavailable`] code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Status {
+    code: Code,
+    message: String,
+}
+
+impl Status {
+    pub fn new<S: Into<String>>(code: Code, message: S) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// Get the status code.
+    #[inline]
+    pub fn code(&self) -> Code {
+        self.code
+    }
+
+    /// Get the status message.
+    #[inline]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let code = headers.get(GRPC_STATUS)?;
+        let code = code
+            .to_str()
+            .ok()
+            .and_then(|v| v.trim().parse::<i32>().ok())
+            .map_or(Code::Unknown, Code::from);
+        let message = headers
+            .get(GRPC_MESSAGE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| percent_decode_str(v).decode_utf8_lossy().into_owned())
+            .unwrap_or_default();
+        Some(Self::new(code, message))
+    }
+
+    fn transport(e: Error) -> Self {
+        Self::new(Code::Unavailable, e.to_string())
+    }
+
+    fn internal<E: fmt::Display>(e: E) -> Self {
+        Self::new(Code::Internal, e.to_string())
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "grpc status {:?}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for Status {}
+
+/// A gRPC channel to a server.
+pub struct Channel {
+    uri: String,
+    headers: HeaderMap,
+    connect_timeout: Option<Duration>,
+    max_decoding_message_size: usize,
+    web: bool,
+    // all errors generated while building the channel will be deferred and returned when making calls.
+    err: Option<Error>,
+}
+
+impl Channel {
+    /// Create a channel to the server, e.g. `http://localhost:50051`.
+    pub fn new(uri: &str) -> Self {
+        Self {
+            uri: uri.trim_end_matches('/').to_string(),
+            headers: HeaderMap::new(),
+            connect_timeout: None,
+            max_decoding_message_size: DEFAULT_MAX_DECODING_MESSAGE_SIZE,
+            web: false,
+            err: None,
+        }
+    }
+
+    /// Use the gRPC-Web protocol, where the trailers are sent at the end of the body.
+    ///
+    /// Default value: false.
+    #[inline]
+    pub fn web(mut self, web: bool) -> Self {
+        self.web = web;
+        self
+    }
+
+    /// Add a metadata header sent with every call.
+    pub fn header<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: IntoHeaderName,
+        V: TryInto<HeaderValue>,
+        <V as TryInto<HeaderValue>>::Error: Into<Error>,
+    {
+        match value.try_into().map_err(|e| e.into()) {
+            Ok(v) => {
+                self.headers.insert(key, v);
+            }
+            Err(e) => self.err = Some(e),
+        }
+        self
+    }
+
+    /// Set the timeout for the initial connect to the server.
+    #[inline]
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the maximum size of a response message, a larger message fails the call with
+    /// [`Code::ResourceExhausted`].
+    ///
+    /// Default value: 4 MiB.
+    #[inline]
+    pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+        self.max_decoding_message_size = limit;
+        self
+    }
+
+    /// Make a unary call, `path` is in the form of `/package.Service/Method`.
+    pub fn unary<Req, Resp>(&self, path: &str, request: &Req) -> Result<Resp, Status>
+    where
+        Req: Message,
+        Resp: Message + Default,
+    {
+        let mut stream = self.server_streaming::<Req, Resp>(path, request)?;
+        let message = match stream.next() {
+            Some(message) => message?,
+            None => return Err(Status::internal("missing response message")),
+        };
+        match stream.next() {
+            Some(Ok(_)) => Err(Status::internal("too many response messages")),
+            Some(Err(status)) => Err(status),
+            None => Ok(message),
+        }
+    }
+
+    /// Make a server streaming call, returning an iterator over the response messages.
+    ///
+    /// A non-OK status sent by the server is returned as the last item.
+    pub fn server_streaming<Req, Resp>(
+        &self,
+        path: &str,
+        request: &Req,
+    ) -> Result<Streaming<Resp>, Status>
+    where
+        Req: Message,
+        Resp: Message + Default,
+    {
+        if let Some(e) = &self.err {
+            return Err(Status::internal(e));
+        }
+
+        let content_type = if self.web {
+            GRPC_WEB_CONTENT_TYPE
+        } else {
+            GRPC_CONTENT_TYPE
+        };
+        let mut builder = Client::new()
+            .post(&format!("{}{}", self.uri, path))
+            .headers(self.headers.iter().map(|(k, v)| (k, v.clone())))
+            .header(CONTENT_TYPE, content_type)
+            .header(ACCEPT, content_type)
+            .body(encode_frame(request));
+        builder = if self.web {
+            builder.header("x-grpc-web", "1")
+        } else {
+            builder.header(TE, "trailers")
+        };
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        let response = builder.send().map_err(Status::transport)?;
+
+        if response.status_code() != 200 {
+            return Err(Status::new(
+                Code::from_http_status(response.status_code()),
+                format!("unexpected HTTP status code {}", response.status_code()),
+            ));
+        }
+        let response_type = response
+            .header(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        if !response_type.starts_with("application/grpc") {
+            return Err(Status::new(
+                Code::Unknown,
+                format!("unexpected Content-Type: {response_type}"),
+            ));
+        }
+
+        Ok(Streaming::new(response, self.max_decoding_message_size))
+    }
+}
+
+/// An iterator over the messages of a server streaming call.
+pub struct Streaming<T> {
+    response: Response,
+    max_message_size: usize,
+    // the status of a trailers-only response, returned by the first call to next
+    pending: Option<Status>,
+    // the final status, once it is known
+    status: Option<Status>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Message + Default> Streaming<T> {
+    fn new(response: Response, max_message_size: usize) -> Self {
+        // a trailers-only response carries the status in the headers
+        let pending = Status::from_headers(response.headers());
+        Self {
+            response,
+            max_message_size,
+            pending,
+            status: None,
+            _marker: PhantomData,
+        }
+    }
+
+    fn finish(&mut self, status: Option<Status>) -> Option<Result<T, Status>> {
+        let status = status.unwrap_or_else(|| Status::internal("missing grpc-status"));
+        self.status = Some(status.clone());
+        match status.code {
+            Code::Ok => None,
+            _ => Some(Err(status)),
+        }
+    }
+}
+
+impl<T: Message + Default> Iterator for Streaming<T> {
+    type Item = Result<T, Status>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.status.is_some() {
+            return None;
+        }
+        if let Some(status) = self.pending.take() {
+            return self.finish(Some(status));
+        }
+
+        match read_frame(&mut self.response, self.max_message_size) {
+            Ok(Some((flag, data))) if flag & FLAG_TRAILERS != 0 => {
+                self.finish(Status::from_headers(&parse_trailers(&data)))
+            }
+            Ok(Some((flag, _))) if flag & FLAG_COMPRESSED != 0 => self.finish(Some(
+                Status::internal("compressed messages are not supported"),
+            )),
+            Ok(Some((_, data))) => Some(T::decode(data.as_slice()).map_err(Status::internal)),
+            Ok(None) => match self.response.trailers() {
+                Ok(trailers) => {
+                    let status = trailers.and_then(Status::from_headers);
+                    self.finish(status)
+                }
+                Err(e) => self.finish(Some(Status::transport(e))),
+            },
+            Err(status) => self.finish(Some(status)),
+        }
+    }
+}
+
+fn encode_frame<M: Message>(message: &M) -> Vec<u8> {
+    let len = message.encoded_len();
+    let mut buf = Vec::with_capacity(len + 5);
+    buf.push(0);
+    buf.extend_from_slice(&(len as u32).to_be_bytes());
+    message
+        .encode(&mut buf)
+        .expect("buffer has sufficient capacity");
+    buf
+}
+
+// Read a length-prefixed message, returning None at the end of the stream.
+//
+// The length is checked against the maximum size before reading, and the buffer only grows as
+// the data arrives, so a bogus length can't allocate more than what is actually sent.
+fn read_frame<R: Read>(reader: &mut R, max_size: usize) -> Result<Option<(u8, Vec<u8>)>, Status> {
+    let transport = |e: io::Error| Status::transport(e.into());
+    let mut header = [0; 5];
+    if reader.read(&mut header[..1]).map_err(transport)? == 0 {
+        return Ok(None);
+    }
+    reader.read_exact(&mut header[1..]).map_err(transport)?;
+    let len = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+    if len > max_size {
+        return Err(Status::new(
+            Code::ResourceExhausted,
+            format!("message of {len} bytes exceeds the maximum of {max_size} bytes"),
+        ));
+    }
+    let mut data = vec![];
+    reader
+        .take(len as u64)
+        .read_to_end(&mut data)
+        .map_err(transport)?;
+    if data.len() < len {
+        return Err(transport(io::ErrorKind::UnexpectedEof.into()));
+    }
+    Ok(Some((header[0], data)))
+}
+
+// gRPC-Web trailers are encoded as an HTTP/1 header block.
+fn parse_trailers(data: &[u8]) -> HeaderMap {
+    String::from_utf8_lossy(data)
+        .split("\r\n")
+        .filter_map(|line| line.split_once(':'))
+        .filter_map(|(k, v)| {
+            Some((
+                k.trim().to_ascii_lowercase().parse().ok()?,
+                v.trim().parse().ok()?,
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame() -> Result<(), Status> {
+        let frame = encode_frame(&String::from("hello"));
+        assert_eq!(frame, b"\x00\x00\x00\x00\x07\x0a\x05hello");
+
+        let data = [frame.as_slice(), b"\x80\x00\x00\x00\x00"].concat();
+        let mut reader = data.as_slice();
+        let (flag, data) = read_frame(&mut reader, 16)?.unwrap();
+        assert_eq!(flag, 0);
+        assert_eq!(String::decode(data.as_slice()).unwrap(), "hello");
+        assert_eq!(read_frame(&mut reader, 16)?, Some((FLAG_TRAILERS, vec![])));
+        assert_eq!(read_frame(&mut reader, 16)?, None);
+
+        let mut truncated = &frame[..6];
+        assert_eq!(
+            read_frame(&mut truncated, 16).unwrap_err().code(),
+            Code::Unavailable
+        );
+
+        let mut reader = frame.as_slice();
+        assert_eq!(
+            read_frame(&mut reader, 6).unwrap_err().code(),
+            Code::ResourceExhausted
+        );
+        // the length is checked before reading the message
+        let mut reader = &b"\x00\xff\xff\xff\xff"[..];
+        assert_eq!(
+            read_frame(&mut reader, DEFAULT_MAX_DECODING_MESSAGE_SIZE)
+                .unwrap_err()
+                .code(),
+            Code::ResourceExhausted
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_status() {
+        let headers = parse_trailers(b"Grpc-Status: 5\r\ngrpc-message: not%20found\r\n");
+        assert_eq!(
+            Status::from_headers(&headers),
+            Some(Status::new(Code::NotFound, "not found"))
+        );
+
+        let headers = parse_trailers(b"grpc-status: 0\r\n");
+        assert_eq!(
+            Status::from_headers(&headers),
+            Some(Status::new(Code::Ok, ""))
+        );
+
+        assert_eq!(Status::from_headers(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn test_trailers_only() {
+        let response = Response::builder()
+            .header(CONTENT_TYPE, GRPC_CONTENT_TYPE)
+            .header(GRPC_STATUS, "5")
+            .header("grpc-message", "not found")
+            .build()
+            .unwrap();
+        let mut stream = Streaming::<String>::new(response, DEFAULT_MAX_DECODING_MESSAGE_SIZE);
+        assert_eq!(
+            stream.next(),
+            Some(Err(Status::new(Code::NotFound, "not found")))
+        );
+        assert_eq!(stream.next(), None);
+
+        let response = Response::builder()
+            .header(GRPC_STATUS, "0")
+            .build()
+            .unwrap();
+        let mut stream = Streaming::<String>::new(response, DEFAULT_MAX_DECODING_MESSAGE_SIZE);
+        assert_eq!(stream.next(), None);
+    }
+}
@@ -0,0 +1,197 @@
+use super::{list, parse_seconds, Header};
+use crate::header::{self, HeaderName, HeaderValue};
+use anyhow::Result;
+use std::time::Duration;
+
+/// The `Cache-Control` header.
+///
+/// The directives are public fields, and can be set with the builder methods of the same name:
+///
+/// ```
+/// # use std::time::Duration;
+/// # use waki::headers::CacheControl;
+/// let cache_control = CacheControl::new().private().max_age(Duration::from_secs(300));
+/// assert_eq!(cache_control.max_age, Some(Duration::from_secs(300)));
+/// ```
+///
+/// Unknown directives are ignored, durations have a precision of seconds.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheControl {
+    pub no_cache: bool,
+    pub no_store: bool,
+    pub no_transform: bool,
+    pub only_if_cached: bool,
+    pub must_revalidate: bool,
+    pub proxy_revalidate: bool,
+    pub must_understand: bool,
+    pub public: bool,
+    pub private: bool,
+    pub immutable: bool,
+    pub max_age: Option<Duration>,
+    pub s_maxage: Option<Duration>,
+    /// `max-stale` without a value is represented as [`Duration::MAX`].
+    pub max_stale: Option<Duration>,
+    pub min_fresh: Option<Duration>,
+    pub stale_while_revalidate: Option<Duration>,
+    pub stale_if_error: Option<Duration>,
+}
+
+macro_rules! flags {
+    ($($field:ident),+ $(,)?) => ($(
+        #[inline]
+        pub fn $field(mut self) -> Self {
+            self.$field = true;
+            self
+        }
+    )+)
+}
+
+macro_rules! durations {
+    ($($field:ident),+ $(,)?) => ($(
+        #[inline]
+        pub fn $field(mut self, duration: Duration) -> Self {
+            self.$field = Some(duration);
+            self
+        }
+    )+)
+}
+
+impl CacheControl {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    flags!(
+        no_cache,
+        no_store,
+        no_transform,
+        only_if_cached,
+        must_revalidate,
+        proxy_revalidate,
+        must_understand,
+        public,
+        private,
+        immutable,
+    );
+
+    durations!(
+        max_age,
+        s_maxage,
+        max_stale,
+        min_fresh,
+        stale_while_revalidate,
+        stale_if_error,
+    );
+}
+
+impl Header for CacheControl {
+    fn name() -> HeaderName {
+        header::CACHE_CONTROL
+    }
+
+    fn decode<'a, I: Iterator<Item = &'a HeaderValue>>(values: I) -> Result<Self> {
+        let mut cc = Self::new();
+        for directive in list(values)? {
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (directive, None),
+            };
+            let seconds =
+                || -> Result<Duration> { parse_seconds(&Self::name(), value.unwrap_or_default()) };
+            match name.to_ascii_lowercase().as_str() {
+                // `no-cache` and `private` may list header names, which are not kept
+                "no-cache" => cc.no_cache = true,
+                "no-store" => cc.no_store = true,
+                "no-transform" => cc.no_transform = true,
+                "only-if-cached" => cc.only_if_cached = true,
+                "must-revalidate" => cc.must_revalidate = true,
+                "proxy-revalidate" => cc.proxy_revalidate = true,
+                "must-understand" => cc.must_understand = true,
+                "public" => cc.public = true,
+                "private" => cc.private = true,
+                "immutable" => cc.immutable = true,
+                "max-age" => cc.max_age = Some(seconds()?),
+                "s-maxage" => cc.s_maxage = Some(seconds()?),
+                "max-stale" if value.is_none() => cc.max_stale = Some(Duration::MAX),
+                "max-stale" => cc.max_stale = Some(seconds()?),
+                "min-fresh" => cc.min_fresh = Some(seconds()?),
+                "stale-while-revalidate" => cc.stale_while_revalidate = Some(seconds()?),
+                "stale-if-error" => cc.stale_if_error = Some(seconds()?),
+                _ => {}
+            }
+        }
+        Ok(cc)
+    }
+
+    fn encode(&self) -> Result<HeaderValue> {
+        let flags = [
+            (self.no_cache, "no-cache"),
+            (self.no_store, "no-store"),
+            (self.no_transform, "no-transform"),
+            (self.only_if_cached, "only-if-cached"),
+            (self.must_revalidate, "must-revalidate"),
+            (self.proxy_revalidate, "proxy-revalidate"),
+            (self.must_understand, "must-understand"),
+            (self.public, "public"),
+            (self.private, "private"),
+            (self.immutable, "immutable"),
+        ];
+        let durations = [
+            (self.max_age, "max-age"),
+            (self.s_maxage, "s-maxage"),
+            (self.max_stale, "max-stale"),
+            (self.min_fresh, "min-fresh"),
+            (self.stale_while_revalidate, "stale-while-revalidate"),
+            (self.stale_if_error, "stale-if-error"),
+        ];
+        let directives = flags
+            .into_iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, name)| name.to_string())
+            .chain(durations.into_iter().filter_map(|(duration, name)| {
+                Some(match duration? {
+                    Duration::MAX => name.to_string(),
+                    duration => format!("{}={}", name, duration.as_secs()),
+                })
+            }))
+            .collect::<Vec<_>>();
+        Ok(HeaderValue::from_str(&directives.join(", "))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headers::tests::{decode, encode};
+
+    #[test]
+    fn test_cache_control() {
+        assert_eq!(
+            decode::<CacheControl>(&[
+                "public, Max-Age=\"60\", no-cache=\"Set-Cookie\"",
+                "stale-while-revalidate=30, max-stale, x-unknown=1"
+            ])
+            .unwrap(),
+            CacheControl::new()
+                .public()
+                .no_cache()
+                .max_age(Duration::from_secs(60))
+                .max_stale(Duration::MAX)
+                .stale_while_revalidate(Duration::from_secs(30))
+        );
+        assert!(decode::<CacheControl>(&["max-age=-1"]).is_err());
+        assert!(decode::<CacheControl>(&["max-age"]).is_err());
+
+        assert_eq!(
+            encode(
+                CacheControl::new()
+                    .no_store()
+                    .private()
+                    .max_age(Duration::from_secs(0))
+                    .max_stale(Duration::MAX)
+            ),
+            "no-store, private, max-age=0, max-stale"
+        );
+        assert_eq!(encode(CacheControl::new()), "");
+    }
+}
@@ -0,0 +1,308 @@
+use super::{invalid, single, Header};
+use crate::header::{self, HeaderName, HeaderValue};
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+/// An entity tag, as used by the `ETag`, `If-Match`, `If-None-Match` and `If-Range` headers.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EntityTag {
+    weak: bool,
+    tag: String,
+}
+
+impl EntityTag {
+    /// Create a strong entity tag, failing if the tag contains a `"` or non-visible characters.
+    pub fn strong<S: Into<String>>(tag: S) -> Result<Self> {
+        Self::new(false, tag.into())
+    }
+
+    /// Create a weak entity tag, failing if the tag contains a `"` or non-visible characters.
+    pub fn weak<S: Into<String>>(tag: S) -> Result<Self> {
+        Self::new(true, tag.into())
+    }
+
+    /// Compute a strong entity tag from the content, using its length and 64-bit FNV-1a hash.
+    ///
+    /// ```
+    /// # use waki::headers::EntityTag;
+    /// assert_eq!(EntityTag::from_content(b"hello").to_string(), "\"5-a430d84680aabd0b\"");
+    /// ```
+    pub fn from_content(data: &[u8]) -> Self {
+        Self {
+            weak: false,
+            tag: format!("{:x}-{:016x}", data.len(), fnv1a(data)),
+        }
+    }
+
+    /// Compute a weak entity tag from the content, see [`EntityTag::from_content`].
+    pub fn weak_from_content(data: &[u8]) -> Self {
+        Self {
+            weak: true,
+            ..Self::from_content(data)
+        }
+    }
+
+    fn new(weak: bool, tag: String) -> Result<Self> {
+        // etagc = %x21 / %x23-7E / obs-text
+        if tag.bytes().any(|b| b == b'"' || b <= b' ' || b == 0x7f) {
+            return Err(anyhow!("invalid entity tag: {:?}", tag));
+        }
+        Ok(Self { weak, tag })
+    }
+
+    /// Get the opaque tag, without quotes.
+    #[inline]
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    #[inline]
+    pub fn is_weak(&self) -> bool {
+        self.weak
+    }
+
+    /// Strong comparison: both tags must be strong and identical.
+    pub fn strong_eq(&self, other: &EntityTag) -> bool {
+        !self.weak && !other.weak && self.tag == other.tag
+    }
+
+    /// Weak comparison: the tags must be identical, whether they are weak or not.
+    pub fn weak_eq(&self, other: &EntityTag) -> bool {
+        self.tag == other.tag
+    }
+}
+
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+impl fmt::Display for EntityTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.weak {
+            write!(f, "W/")?;
+        }
+        write!(f, "\"{}\"", self.tag)
+    }
+}
+
+impl FromStr for EntityTag {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (weak, tag) = match s.strip_prefix("W/") {
+            Some(tag) => (true, tag),
+            None => (false, s),
+        };
+        match tag.strip_prefix('"').and_then(|tag| tag.strip_suffix('"')) {
+            Some(tag) => Self::new(weak, tag.to_string()),
+            None => Err(anyhow!("invalid entity tag: {:?}", s)),
+        }
+    }
+}
+
+/// Parse a list of entity tags, which may contain commas inside the quotes.
+fn parse_list<'a, I: Iterator<Item = &'a HeaderValue>>(
+    name: &HeaderName,
+    values: I,
+) -> Result<Vec<EntityTag>> {
+    let mut tags = vec![];
+    for value in values {
+        let mut rest = value.to_str()?.trim();
+        while !rest.is_empty() {
+            let start = if rest.starts_with("W/") { 3 } else { 1 };
+            let end = rest
+                .get(start..)
+                .and_then(|s| s.find('"'))
+                .ok_or_else(|| invalid(name, rest))?;
+            tags.push(rest[..start + end + 1].parse()?);
+            rest = rest[start + end + 1..].trim_start();
+            match rest.strip_prefix(',') {
+                Some(r) => rest = r.trim_start(),
+                None if rest.is_empty() => {}
+                None => return Err(invalid(name, rest)),
+            }
+        }
+    }
+    Ok(tags)
+}
+
+fn encode_list(tags: &[EntityTag]) -> Result<HeaderValue> {
+    Ok(HeaderValue::from_str(
+        &tags
+            .iter()
+            .map(|tag| tag.to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+    )?)
+}
+
+/// The `ETag` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ETag(pub EntityTag);
+
+impl Header for ETag {
+    fn name() -> HeaderName {
+        header::ETAG
+    }
+
+    fn decode<'a, I: Iterator<Item = &'a HeaderValue>>(values: I) -> Result<Self> {
+        Ok(Self(single(&Self::name(), values)?.parse()?))
+    }
+
+    fn encode(&self) -> Result<HeaderValue> {
+        Ok(HeaderValue::from_str(&self.0.to_string())?)
+    }
+}
+
+macro_rules! condition_header {
+    ($(#[$doc:meta] $t:ident => $name:ident),+ $(,)?) => ($(
+        #[$doc]
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub enum $t {
+            /// `*`, any current representation.
+            Any,
+            Tags(Vec<EntityTag>),
+        }
+
+        impl Header for $t {
+            fn name() -> HeaderName {
+                header::$name
+            }
+
+            fn decode<'a, I: Iterator<Item = &'a HeaderValue>>(values: I) -> Result<Self> {
+                let values = values.collect::<Vec<_>>();
+                if values.iter().any(|v| v.as_bytes().trim_ascii() == b"*") {
+                    return Ok(Self::Any);
+                }
+                Ok(Self::Tags(parse_list(&Self::name(), values.into_iter())?))
+            }
+
+            fn encode(&self) -> Result<HeaderValue> {
+                match self {
+                    Self::Any => Ok(HeaderValue::from_static("*")),
+                    Self::Tags(tags) => encode_list(tags),
+                }
+            }
+        }
+    )+)
+}
+
+condition_header!(
+    /// The `If-Match` header.
+    IfMatch => IF_MATCH,
+    /// The `If-None-Match` header.
+    IfNoneMatch => IF_NONE_MATCH,
+);
+
+/// The `If-Range` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IfRange {
+    ETag(EntityTag),
+    Date(SystemTime),
+}
+
+impl Header for IfRange {
+    fn name() -> HeaderName {
+        header::IF_RANGE
+    }
+
+    fn decode<'a, I: Iterator<Item = &'a HeaderValue>>(values: I) -> Result<Self> {
+        let value = single(&Self::name(), values)?;
+        if value.starts_with('"') || value.starts_with("W/") {
+            Ok(Self::ETag(value.parse()?))
+        } else {
+            Ok(Self::Date(httpdate::parse_http_date(value)?))
+        }
+    }
+
+    fn encode(&self) -> Result<HeaderValue> {
+        let value = match self {
+            Self::ETag(tag) => tag.to_string(),
+            Self::Date(time) => httpdate::fmt_http_date(*time),
+        };
+        Ok(HeaderValue::from_str(&value)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headers::tests::{decode, encode};
+    use std::time::Duration;
+
+    #[test]
+    fn test_entity_tag() {
+        let strong = EntityTag::strong("abc").unwrap();
+        let weak = EntityTag::weak("abc").unwrap();
+        assert_eq!(strong.to_string(), "\"abc\"");
+        assert_eq!(weak.to_string(), "W/\"abc\"");
+        assert_eq!("W/\"abc\"".parse::<EntityTag>().unwrap(), weak);
+        assert_eq!("\"\"".parse::<EntityTag>().unwrap().tag(), "");
+
+        assert!(strong.strong_eq(&strong));
+        assert!(!strong.strong_eq(&weak));
+        assert!(!weak.strong_eq(&weak));
+        assert!(weak.weak_eq(&strong));
+        assert!(!weak.weak_eq(&EntityTag::weak("abd").unwrap()));
+
+        let tag = EntityTag::from_content(b"");
+        assert_eq!(tag.tag(), "0-cbf29ce484222325");
+        assert!(tag.strong_eq(&EntityTag::from_content(b"")));
+        assert!(!tag.weak_eq(&EntityTag::from_content(b"a")));
+        assert!(EntityTag::weak_from_content(b"").is_weak());
+
+        assert!(EntityTag::strong("a\"b").is_err());
+        assert!(EntityTag::strong("a b").is_err());
+        assert!("abc".parse::<EntityTag>().is_err());
+        assert!("w/\"abc\"".parse::<EntityTag>().is_err());
+    }
+
+    #[test]
+    fn test_etag() {
+        let ETag(tag) = decode(&["W/\"v1\""]).unwrap();
+        assert_eq!(tag, EntityTag::weak("v1").unwrap());
+        assert_eq!(encode(ETag(EntityTag::strong("v1").unwrap())), "\"v1\"");
+    }
+
+    #[test]
+    fn test_conditions() {
+        assert_eq!(
+            decode::<IfNoneMatch>(&["\"a,b\", W/\"c\"", "\"d\""]).unwrap(),
+            IfNoneMatch::Tags(vec![
+                EntityTag::strong("a,b").unwrap(),
+                EntityTag::weak("c").unwrap(),
+                EntityTag::strong("d").unwrap(),
+            ])
+        );
+        assert_eq!(decode::<IfMatch>(&[" * "]).unwrap(), IfMatch::Any);
+        assert!(decode::<IfMatch>(&["\"a\" \"b\""]).is_err());
+        assert!(decode::<IfMatch>(&["\"a"]).is_err());
+        assert!(decode::<IfMatch>(&["a"]).is_err());
+        assert_eq!(
+            encode(IfMatch::Tags(vec![
+                EntityTag::strong("a").unwrap(),
+                EntityTag::weak("b").unwrap()
+            ])),
+            "\"a\", W/\"b\""
+        );
+        assert_eq!(encode(IfNoneMatch::Any), "*");
+    }
+
+    #[test]
+    fn test_if_range() {
+        assert_eq!(
+            decode::<IfRange>(&["\"v1\""]).unwrap(),
+            IfRange::ETag(EntityTag::strong("v1").unwrap())
+        );
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(784111777);
+        assert_eq!(
+            decode::<IfRange>(&["Sun, 06 Nov 1994 08:49:37 GMT"]).unwrap(),
+            IfRange::Date(time)
+        );
+        assert_eq!(encode(IfRange::Date(time)), "Sun, 06 Nov 1994 08:49:37 GMT");
+    }
+}
@@ -0,0 +1,342 @@
+//! Typed headers.
+//!
+//! Instead of parsing raw [`HeaderValue`]s, headers can be read and written as types
+//! implementing [`Header`]:
+//!
+//! ```
+//! # use std::time::Duration;
+//! # use waki::{headers::{CacheControl, ContentType}, ErrorCode, Request, Response};
+//! fn handler(req: Request) -> Result<Response, ErrorCode> {
+//!     if let Some(ContentType(mime)) = req.typed_header::<ContentType>() {
+//!         println!("{}", mime);
+//!     }
+//!     Response::builder()
+//!         .typed_header(CacheControl::new().public().max_age(Duration::from_secs(60)))
+//!         .build()
+//! }
+//! ```
+
+mod cache_control;
+mod entity;
+mod range;
+
+pub use self::{
+    cache_control::CacheControl,
+    entity::{ETag, EntityTag, IfMatch, IfNoneMatch, IfRange},
+    range::{AcceptRanges, ByteRange, ContentRange, Range},
+};
+
+use crate::header::{self, HeaderName, HeaderValue};
+use anyhow::{anyhow, Error, Result};
+use mime::Mime;
+use std::time::{Duration, SystemTime};
+
+/// A header that can be decoded from and encoded into header values.
+pub trait Header: Sized {
+    /// Get the name of the header.
+    fn name() -> HeaderName;
+
+    /// Decode the header from all of its values, in order.
+    fn decode<'a, I: Iterator<Item = &'a HeaderValue>>(values: I) -> Result<Self>;
+
+    /// Encode the header into a value.
+    fn encode(&self) -> Result<HeaderValue>;
+}
+
+/// Get the only value of the header as a string.
+fn single<'a, I: Iterator<Item = &'a HeaderValue>>(
+    name: &HeaderName,
+    mut values: I,
+) -> Result<&'a str> {
+    match (values.next(), values.next()) {
+        (Some(value), None) => Ok(value.to_str()?.trim()),
+        (None, _) => Err(anyhow!("missing {} header", name)),
+        (Some(_), Some(_)) => Err(anyhow!("multiple {} headers", name)),
+    }
+}
+
+/// Split the values of a list header into its non-empty elements.
+fn list<'a, I: Iterator<Item = &'a HeaderValue>>(values: I) -> Result<Vec<&'a str>> {
+    let mut items = vec![];
+    for value in values {
+        items.extend(
+            value
+                .to_str()?
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty()),
+        );
+    }
+    Ok(items)
+}
+
+fn invalid(name: &HeaderName, value: &str) -> Error {
+    anyhow!("invalid {} header: {:?}", name, value)
+}
+
+/// The `Content-Type` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentType(pub Mime);
+
+impl Header for ContentType {
+    fn name() -> HeaderName {
+        header::CONTENT_TYPE
+    }
+
+    fn decode<'a, I: Iterator<Item = &'a HeaderValue>>(values: I) -> Result<Self> {
+        let value = single(&Self::name(), values)?;
+        Ok(Self(value.parse()?))
+    }
+
+    fn encode(&self) -> Result<HeaderValue> {
+        Ok(HeaderValue::from_str(self.0.as_ref())?)
+    }
+}
+
+impl From<Mime> for ContentType {
+    #[inline]
+    fn from(mime: Mime) -> Self {
+        Self(mime)
+    }
+}
+
+/// The `Content-Length` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentLength(pub u64);
+
+impl Header for ContentLength {
+    fn name() -> HeaderName {
+        header::CONTENT_LENGTH
+    }
+
+    fn decode<'a, I: Iterator<Item = &'a HeaderValue>>(values: I) -> Result<Self> {
+        let value = single(&Self::name(), values)?;
+        // the value must be digits only, which `parse` alone does not enforce because of `+`
+        if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid(&Self::name(), value));
+        }
+        Ok(Self(value.parse()?))
+    }
+
+    fn encode(&self) -> Result<HeaderValue> {
+        Ok(self.0.into())
+    }
+}
+
+/// The `Location` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location(pub String);
+
+impl Header for Location {
+    fn name() -> HeaderName {
+        header::LOCATION
+    }
+
+    fn decode<'a, I: Iterator<Item = &'a HeaderValue>>(values: I) -> Result<Self> {
+        Ok(Self(single(&Self::name(), values)?.to_string()))
+    }
+
+    fn encode(&self) -> Result<HeaderValue> {
+        Ok(HeaderValue::from_str(&self.0)?)
+    }
+}
+
+/// The `Age` header, with a precision of seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Age(pub Duration);
+
+impl Header for Age {
+    fn name() -> HeaderName {
+        header::AGE
+    }
+
+    fn decode<'a, I: Iterator<Item = &'a HeaderValue>>(values: I) -> Result<Self> {
+        Ok(Self(parse_seconds(
+            &Self::name(),
+            single(&Self::name(), values)?,
+        )?))
+    }
+
+    fn encode(&self) -> Result<HeaderValue> {
+        Ok(self.0.as_secs().into())
+    }
+}
+
+/// Parse delta-seconds, values that overflow are capped.
+fn parse_seconds(name: &HeaderName, value: &str) -> Result<Duration> {
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid(name, value));
+    }
+    Ok(Duration::from_secs(value.parse().unwrap_or(u64::MAX)))
+}
+
+/// The `Vary` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Vary {
+    /// `*`, the response varies on more than the request headers.
+    Any,
+    /// The request headers the response varies on.
+    Headers(Vec<HeaderName>),
+}
+
+impl Header for Vary {
+    fn name() -> HeaderName {
+        header::VARY
+    }
+
+    fn decode<'a, I: Iterator<Item = &'a HeaderValue>>(values: I) -> Result<Self> {
+        let items = list(values)?;
+        if items.contains(&"*") {
+            return Ok(Self::Any);
+        }
+        Ok(Self::Headers(
+            items
+                .into_iter()
+                .map(HeaderName::try_from)
+                .collect::<Result<_, _>>()?,
+        ))
+    }
+
+    fn encode(&self) -> Result<HeaderValue> {
+        match self {
+            Self::Any => Ok(HeaderValue::from_static("*")),
+            Self::Headers(names) => Ok(HeaderValue::from_str(
+                &names
+                    .iter()
+                    .map(HeaderName::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            )?),
+        }
+    }
+}
+
+macro_rules! date_header {
+    ($(#[$doc:meta] $t:ident => $name:ident),+ $(,)?) => ($(
+        #[$doc]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub struct $t(pub SystemTime);
+
+        impl Header for $t {
+            fn name() -> HeaderName {
+                header::$name
+            }
+
+            fn decode<'a, I: Iterator<Item = &'a HeaderValue>>(values: I) -> Result<Self> {
+                Ok(Self(httpdate::parse_http_date(single(&Self::name(), values)?)?))
+            }
+
+            fn encode(&self) -> Result<HeaderValue> {
+                Ok(HeaderValue::from_str(&httpdate::fmt_http_date(self.0))?)
+            }
+        }
+
+        impl From<SystemTime> for $t {
+            #[inline]
+            fn from(time: SystemTime) -> Self {
+                Self(time)
+            }
+        }
+    )+)
+}
+
+date_header!(
+    /// The `Date` header.
+    Date => DATE,
+    /// The `Expires` header.
+    Expires => EXPIRES,
+    /// The `Last-Modified` header.
+    LastModified => LAST_MODIFIED,
+    /// The `If-Modified-Since` header.
+    IfModifiedSince => IF_MODIFIED_SINCE,
+    /// The `If-Unmodified-Since` header.
+    IfUnmodifiedSince => IF_UNMODIFIED_SINCE,
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[track_caller]
+    pub(super) fn decode<H: Header>(values: &[&'static str]) -> Result<H> {
+        H::decode(
+            values
+                .iter()
+                .map(|v| HeaderValue::from_static(v))
+                .collect::<Vec<_>>()
+                .iter(),
+        )
+    }
+
+    #[track_caller]
+    pub(super) fn encode<H: Header>(header: H) -> String {
+        header.encode().unwrap().to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_content_type() {
+        let ContentType(mime) = decode(&["text/html; charset=utf-8"]).unwrap();
+        assert_eq!(mime, mime::TEXT_HTML_UTF_8);
+        assert_eq!(
+            encode(ContentType(mime::APPLICATION_JSON)),
+            "application/json"
+        );
+        assert!(decode::<ContentType>(&[]).is_err());
+        assert!(decode::<ContentType>(&["text/html", "text/plain"]).is_err());
+    }
+
+    #[test]
+    fn test_content_length() {
+        assert_eq!(decode::<ContentLength>(&["42"]).unwrap(), ContentLength(42));
+        assert!(decode::<ContentLength>(&["+42"]).is_err());
+        assert!(decode::<ContentLength>(&["-1"]).is_err());
+        assert_eq!(encode(ContentLength(42)), "42");
+    }
+
+    #[test]
+    fn test_vary() {
+        assert_eq!(
+            decode::<Vary>(&["Accept-Encoding, accept", "Origin"]).unwrap(),
+            Vary::Headers(vec![
+                header::ACCEPT_ENCODING,
+                header::ACCEPT,
+                header::ORIGIN
+            ])
+        );
+        assert_eq!(decode::<Vary>(&["Accept, *"]).unwrap(), Vary::Any);
+        assert_eq!(
+            encode(Vary::Headers(vec![header::ACCEPT, header::ORIGIN])),
+            "accept, origin"
+        );
+    }
+
+    #[test]
+    fn test_date() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(784111777);
+        assert_eq!(
+            decode::<LastModified>(&["Sun, 06 Nov 1994 08:49:37 GMT"]).unwrap(),
+            LastModified(time)
+        );
+        // obsolete formats are accepted
+        assert_eq!(
+            decode::<IfModifiedSince>(&["Sunday, 06-Nov-94 08:49:37 GMT"]).unwrap(),
+            IfModifiedSince(time)
+        );
+        assert!(decode::<Expires>(&["0"]).is_err());
+        assert_eq!(encode(Date(time)), "Sun, 06 Nov 1994 08:49:37 GMT");
+    }
+
+    #[test]
+    fn test_age() {
+        assert_eq!(
+            decode::<Age>(&["60"]).unwrap(),
+            Age(Duration::from_secs(60))
+        );
+        assert_eq!(
+            decode::<Age>(&["99999999999999999999"]).unwrap(),
+            Age(Duration::from_secs(u64::MAX))
+        );
+        assert!(decode::<Age>(&["1.5"]).is_err());
+        assert_eq!(encode(Age(Duration::from_millis(1500))), "1");
+    }
+}
@@ -0,0 +1,290 @@
+use super::{invalid, list, single, Header};
+use crate::header::{self, HeaderName, HeaderValue};
+use anyhow::Result;
+use std::fmt;
+
+/// A range in the `Range` header, the positions are inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// `first-last`
+    FromTo(u64, u64),
+    /// `first-`
+    From(u64),
+    /// `-length`, the last bytes.
+    Last(u64),
+}
+
+impl ByteRange {
+    /// Resolve the range against a representation of `len` bytes, returning the inclusive first
+    /// and last positions, or `None` if the range is unsatisfiable.
+    ///
+    /// ```
+    /// # use waki::headers::ByteRange;
+    /// assert_eq!(ByteRange::FromTo(5, 100).resolve(10), Some((5, 9)));
+    /// assert_eq!(ByteRange::Last(3).resolve(10), Some((7, 9)));
+    /// assert_eq!(ByteRange::From(10).resolve(10), None);
+    /// ```
+    pub fn resolve(&self, len: u64) -> Option<(u64, u64)> {
+        match *self {
+            ByteRange::FromTo(first, last) if first < len => Some((first, last.min(len - 1))),
+            ByteRange::From(first) if first < len => Some((first, len - 1)),
+            ByteRange::Last(length) if length > 0 && len > 0 => {
+                Some((len - length.min(len), len - 1))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ByteRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ByteRange::FromTo(first, last) => write!(f, "{first}-{last}"),
+            ByteRange::From(first) => write!(f, "{first}-"),
+            ByteRange::Last(length) => write!(f, "-{length}"),
+        }
+    }
+}
+
+/// The `Range` header, only the `bytes` unit is supported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Range(pub Vec<ByteRange>);
+
+impl Range {
+    /// Create a header requesting the single range.
+    pub fn bytes(range: ByteRange) -> Self {
+        Self(vec![range])
+    }
+}
+
+impl Header for Range {
+    fn name() -> HeaderName {
+        header::RANGE
+    }
+
+    fn decode<'a, I: Iterator<Item = &'a HeaderValue>>(values: I) -> Result<Self> {
+        let name = Self::name();
+        let value = single(&name, values)?;
+        let ranges = match value.split_once('=') {
+            Some((unit, ranges)) if unit.trim().eq_ignore_ascii_case("bytes") => ranges,
+            _ => return Err(invalid(&name, value)),
+        };
+        let parse = |s: &str| {
+            if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid(&name, value));
+            }
+            Ok(s.parse::<u64>()?)
+        };
+        let ranges = ranges
+            .split(',')
+            .map(str::trim)
+            .filter(|range| !range.is_empty())
+            .map(|range| match range.split_once('-') {
+                Some(("", length)) => Ok(ByteRange::Last(parse(length)?)),
+                Some((first, "")) => Ok(ByteRange::From(parse(first)?)),
+                Some((first, last)) => {
+                    let (first, last) = (parse(first)?, parse(last)?);
+                    if first > last {
+                        return Err(invalid(&name, value));
+                    }
+                    Ok(ByteRange::FromTo(first, last))
+                }
+                None => Err(invalid(&name, value)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if ranges.is_empty() {
+            return Err(invalid(&name, value));
+        }
+        Ok(Self(ranges))
+    }
+
+    fn encode(&self) -> Result<HeaderValue> {
+        let ranges = self
+            .0
+            .iter()
+            .map(ByteRange::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        Ok(HeaderValue::from_str(&format!("bytes={ranges}"))?)
+    }
+}
+
+/// The `Content-Range` header, only the `bytes` unit is supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentRange {
+    /// The inclusive first and last positions, `None` for an unsatisfied range.
+    pub range: Option<(u64, u64)>,
+    /// The length of the complete representation, if known.
+    pub complete_length: Option<u64>,
+}
+
+impl ContentRange {
+    /// Create a header for the inclusive range of a representation of `complete_length` bytes.
+    pub fn bytes(first: u64, last: u64, complete_length: Option<u64>) -> Self {
+        Self {
+            range: Some((first, last)),
+            complete_length,
+        }
+    }
+
+    /// Create a header for a 416 response, i.e. `bytes */complete_length`.
+    pub fn unsatisfied(complete_length: u64) -> Self {
+        Self {
+            range: None,
+            complete_length: Some(complete_length),
+        }
+    }
+}
+
+impl Header for ContentRange {
+    fn name() -> HeaderName {
+        header::CONTENT_RANGE
+    }
+
+    fn decode<'a, I: Iterator<Item = &'a HeaderValue>>(values: I) -> Result<Self> {
+        let name = Self::name();
+        let value = single(&name, values)?;
+        let parse = |s: &str| {
+            if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid(&name, value));
+            }
+            Ok(s.parse::<u64>()?)
+        };
+        let (range, complete_length) = value
+            .strip_prefix("bytes ")
+            .and_then(|s| s.split_once('/'))
+            .ok_or_else(|| invalid(&name, value))?;
+        let complete_length = match complete_length {
+            "*" => None,
+            len => Some(parse(len)?),
+        };
+        let range = match range {
+            "*" if complete_length.is_some() => None,
+            range => {
+                let (first, last) = range.split_once('-').ok_or_else(|| invalid(&name, value))?;
+                let (first, last) = (parse(first)?, parse(last)?);
+                if first > last || complete_length.is_some_and(|len| last >= len) {
+                    return Err(invalid(&name, value));
+                }
+                Some((first, last))
+            }
+        };
+        Ok(Self {
+            range,
+            complete_length,
+        })
+    }
+
+    fn encode(&self) -> Result<HeaderValue> {
+        let range = match self.range {
+            Some((first, last)) => format!("{first}-{last}"),
+            None => "*".to_string(),
+        };
+        let complete_length = match self.complete_length {
+            Some(len) => len.to_string(),
+            None => "*".to_string(),
+        };
+        Ok(HeaderValue::from_str(&format!(
+            "bytes {range}/{complete_length}"
+        ))?)
+    }
+}
+
+/// The `Accept-Ranges` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AcceptRanges(pub Vec<String>);
+
+impl AcceptRanges {
+    /// `Accept-Ranges: bytes`
+    pub fn bytes() -> Self {
+        Self(vec!["bytes".to_string()])
+    }
+
+    /// `Accept-Ranges: none`
+    pub fn none() -> Self {
+        Self(vec!["none".to_string()])
+    }
+
+    /// Check whether byte ranges are accepted.
+    pub fn accepts_bytes(&self) -> bool {
+        self.0.iter().any(|unit| unit.eq_ignore_ascii_case("bytes"))
+    }
+}
+
+impl Header for AcceptRanges {
+    fn name() -> HeaderName {
+        header::ACCEPT_RANGES
+    }
+
+    fn decode<'a, I: Iterator<Item = &'a HeaderValue>>(values: I) -> Result<Self> {
+        Ok(Self(list(values)?.into_iter().map(String::from).collect()))
+    }
+
+    fn encode(&self) -> Result<HeaderValue> {
+        Ok(HeaderValue::from_str(&self.0.join(", "))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headers::tests::{decode, encode};
+
+    #[test]
+    fn test_range() {
+        assert_eq!(
+            decode::<Range>(&["bytes=0-499, 500- ,-200"]).unwrap(),
+            Range(vec![
+                ByteRange::FromTo(0, 499),
+                ByteRange::From(500),
+                ByteRange::Last(200)
+            ])
+        );
+        for value in [
+            "bytes=",
+            "bytes=5-1",
+            "bytes=-",
+            "bytes=a-1",
+            "bytes=1",
+            "items=0-1",
+            "bytes=+1-2",
+        ] {
+            assert!(decode::<Range>(&[value]).is_err(), "{value}");
+        }
+        assert_eq!(
+            encode(Range(vec![ByteRange::FromTo(0, 0), ByteRange::Last(1)])),
+            "bytes=0-0,-1"
+        );
+    }
+
+    #[test]
+    fn test_content_range() {
+        assert_eq!(
+            decode::<ContentRange>(&["bytes 0-499/1234"]).unwrap(),
+            ContentRange::bytes(0, 499, Some(1234))
+        );
+        assert_eq!(
+            decode::<ContentRange>(&["bytes 0-499/*"]).unwrap(),
+            ContentRange::bytes(0, 499, None)
+        );
+        assert_eq!(
+            decode::<ContentRange>(&["bytes */1234"]).unwrap(),
+            ContentRange::unsatisfied(1234)
+        );
+        for value in ["bytes */*", "bytes 0-1234/1234", "bytes 2-1/10", "0-1/10"] {
+            assert!(decode::<ContentRange>(&[value]).is_err(), "{value}");
+        }
+        assert_eq!(
+            encode(ContentRange::bytes(0, 499, Some(1234))),
+            "bytes 0-499/1234"
+        );
+        assert_eq!(encode(ContentRange::unsatisfied(10)), "bytes */10");
+    }
+
+    #[test]
+    fn test_accept_ranges() {
+        assert!(decode::<AcceptRanges>(&["Bytes"]).unwrap().accepts_bytes());
+        assert!(!decode::<AcceptRanges>(&["none"]).unwrap().accepts_bytes());
+        assert_eq!(encode(AcceptRanges::bytes()), "bytes");
+    }
+}
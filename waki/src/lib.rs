@@ -33,12 +33,24 @@
 #![cfg_attr(docsrs, feature(doc_auto_cfg))]
 
 mod body;
+pub mod cache;
 mod client;
+pub mod codec;
 mod common;
+mod conditional;
+#[cfg(feature = "fs")]
+pub mod fs;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod headers;
 #[cfg(feature = "multipart")]
 pub mod multipart;
+mod negotiate;
+mod partial;
+mod proxy;
 mod request;
 mod response;
+pub mod sse;
 
 #[doc(hidden)]
 pub mod bindings {
@@ -51,10 +63,13 @@ pub mod bindings {
 }
 
 #[doc(hidden)]
-pub use self::response::handle_response;
+pub use self::response::{handle_error, handle_response};
 pub use self::{
     bindings::wasi::http::types::{ErrorCode, Method},
+    body::{set_body_limit, BodyTooLarge},
     client::Client,
+    negotiate::NotAcceptable,
+    proxy::Proxy,
     request::{Request, RequestBuilder},
     response::{Response, ResponseBuilder},
 };
@@ -75,4 +90,24 @@ pub use self::{
 /// ```
 pub use waki_macros::handler;
 
+/// Embed a directory into the component, as an [`EmbeddedDir`](fs::EmbeddedDir).
+///
+/// The path is relative to the directory of the package's `Cargo.toml`. The MIME types and
+/// entity tags of the files are computed at compile time, and `name.br` and `name.gz` files are
+/// embedded as pre-compressed variants of `name` when it exists.
+///
+/// ```ignore
+/// use waki::{embed_dir, handler, ErrorCode, Request, Response};
+///
+/// #[handler]
+/// fn assets(req: Request) -> Result<Response, ErrorCode> {
+///     embed_dir!("assets").strip_prefix("/static").serve(&req)
+/// }
+/// ```
+///
+/// Files added to or removed from the directory are only picked up when the package is rebuilt.
+#[cfg(feature = "fs")]
+pub use waki_macros::embed_dir;
+
 pub use http::header;
+pub use mime;
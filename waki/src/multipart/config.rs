@@ -0,0 +1,162 @@
+use mime::Mime;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+
+/// Limits and validation rules enforced while parsing a multipart body.
+///
+/// By default nothing is limited.
+///
+/// ```
+/// # use waki::multipart::MultipartConfig;
+/// let config = MultipartConfig::new()
+///     .max_parts(10)
+///     .max_field_bytes(64 * 1024)
+///     .max_file_bytes(10 << 20)
+///     .allowed_mime_types("avatar", [mime::IMAGE_PNG, mime::IMAGE_JPEG])
+///     .required("username");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MultipartConfig {
+    pub(crate) max_parts: Option<usize>,
+    pub(crate) max_field_bytes: Option<u64>,
+    pub(crate) max_file_bytes: Option<u64>,
+    pub(crate) max_total_bytes: Option<u64>,
+    pub(crate) allowed_mime_types: HashMap<String, Vec<Mime>>,
+    pub(crate) required: Vec<String>,
+}
+
+impl MultipartConfig {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Set the maximum number of parts.
+    pub fn max_parts(mut self, max: usize) -> Self {
+        self.max_parts = Some(max);
+        self
+    }
+
+    /// Set the maximum size of a text field, i.e. a part without a filename.
+    pub fn max_field_bytes(mut self, max: u64) -> Self {
+        self.max_field_bytes = Some(max);
+        self
+    }
+
+    /// Set the maximum size of a file, i.e. a part with a filename.
+    pub fn max_file_bytes(mut self, max: u64) -> Self {
+        self.max_file_bytes = Some(max);
+        self
+    }
+
+    /// Set the maximum size of the whole multipart body.
+    pub fn max_total_bytes(mut self, max: u64) -> Self {
+        self.max_total_bytes = Some(max);
+        self
+    }
+
+    /// Restrict the MIME types of the field, wildcards such as `image/*` are supported.
+    ///
+    /// A part without a Content-Type header is considered `text/plain`.
+    pub fn allowed_mime_types<S, I>(mut self, name: S, mimes: I) -> Self
+    where
+        S: Into<String>,
+        I: IntoIterator<Item = Mime>,
+    {
+        self.allowed_mime_types
+            .entry(name.into())
+            .or_default()
+            .extend(mimes);
+        self
+    }
+
+    /// Require the field to be present.
+    pub fn required<S: Into<String>>(mut self, name: S) -> Self {
+        self.required.push(name.into());
+        self
+    }
+
+    pub(crate) fn is_allowed(&self, name: &str, mime: Option<&Mime>) -> bool {
+        let Some(allowed) = self.allowed_mime_types.get(name) else {
+            return true;
+        };
+        let mime = mime.unwrap_or(&mime::TEXT_PLAIN);
+        allowed.iter().any(|allowed| {
+            allowed.type_() == mime::STAR
+                || (allowed.type_() == mime.type_()
+                    && (allowed.subtype() == mime::STAR || allowed.subtype() == mime.subtype()))
+        })
+    }
+}
+
+/// The error returned when a multipart body violates the [`MultipartConfig`].
+///
+/// When reading a [`Field`](super::Field) through [`Read`](std::io::Read), it is wrapped in
+/// an [`io::Error`], use [`MultipartError::from_io`] to get it back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MultipartError {
+    TooManyParts { limit: usize },
+    FieldTooLarge { name: String, limit: u64 },
+    FileTooLarge { name: String, limit: u64 },
+    BodyTooLarge { limit: u64 },
+    UnsupportedMediaType { name: String, mime: String },
+    MissingField { name: String },
+}
+
+impl MultipartError {
+    /// Get the HTTP status code that the error should be answered with.
+    pub fn status_code(&self) -> u16 {
+        match self {
+            MultipartError::TooManyParts { .. }
+            | MultipartError::FieldTooLarge { .. }
+            | MultipartError::FileTooLarge { .. }
+            | MultipartError::BodyTooLarge { .. } => 413,
+            MultipartError::UnsupportedMediaType { .. } => 415,
+            MultipartError::MissingField { .. } => 400,
+        }
+    }
+
+    /// Get the multipart error wrapped in an I/O error.
+    pub fn from_io(e: &io::Error) -> Option<&Self> {
+        e.get_ref().and_then(|e| e.downcast_ref())
+    }
+}
+
+impl fmt::Display for MultipartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MultipartError::TooManyParts { limit } => {
+                write!(f, "multipart body exceeds the limit of {limit} parts")
+            }
+            MultipartError::FieldTooLarge { name, limit } => {
+                write!(f, "field {name} exceeds the size limit of {limit} bytes")
+            }
+            MultipartError::FileTooLarge { name, limit } => {
+                write!(f, "file {name} exceeds the size limit of {limit} bytes")
+            }
+            MultipartError::BodyTooLarge { limit } => {
+                write!(f, "multipart body exceeds the size limit of {limit} bytes")
+            }
+            MultipartError::UnsupportedMediaType { name, mime } => {
+                write!(f, "field {name} has an unsupported media type {mime}")
+            }
+            MultipartError::MissingField { name } => write!(f, "missing required field {name}"),
+        }
+    }
+}
+
+impl std::error::Error for MultipartError {}
+
+impl From<crate::BodyTooLarge> for MultipartError {
+    #[inline]
+    fn from(e: crate::BodyTooLarge) -> Self {
+        MultipartError::BodyTooLarge { limit: e.limit() }
+    }
+}
+
+impl From<MultipartError> for io::Error {
+    #[inline]
+    fn from(e: MultipartError) -> Self {
+        io::Error::other(e)
+    }
+}
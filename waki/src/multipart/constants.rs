@@ -1,4 +1,6 @@
 pub const MAX_HEADERS: usize = 32;
+pub const MAX_HEADERS_SIZE: usize = 8 * 1024;
+pub const MAX_PREAMBLE_SIZE: usize = 64 * 1024;
 pub const BOUNDARY_EXT: &str = "--";
 pub const CRLF: &str = "\r\n";
 pub const CRLF_CRLF: &str = "\r\n\r\n";
@@ -0,0 +1,268 @@
+//! Formatting and parsing of the `Content-Disposition` header of multipart parts.
+//!
+//! ref: https://www.rfc-editor.org/rfc/rfc7578#section-4.2, https://www.rfc-editor.org/rfc/rfc5987
+
+use anyhow::{anyhow, Result};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use std::fmt::Write;
+
+// attr-char in RFC 5987, everything else is percent-encoded.
+const ATTR_CHAR: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'!')
+    .remove(b'#')
+    .remove(b'$')
+    .remove(b'&')
+    .remove(b'+')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'^')
+    .remove(b'_')
+    .remove(b'`')
+    .remove(b'|')
+    .remove(b'~');
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ContentDisposition {
+    pub kind: String,
+    pub name: Option<String>,
+    pub filename: Option<String>,
+}
+
+/// Format the value of a Content-Disposition header, e.g. `form-data` or `attachment`.
+///
+/// Parameters are sent as quoted strings, control characters such as CR and LF are rejected so
+/// they can't be used to inject headers. Non-ASCII filenames are additionally sent in the
+/// `filename*` parameter, with an ASCII fallback in `filename`.
+pub fn format(kind: &str, name: Option<&str>, filename: Option<&str>) -> Result<String> {
+    for param in [name, filename].into_iter().flatten() {
+        if param.chars().any(|c| c.is_ascii_control() && c != '\t') {
+            return Err(anyhow!(
+                "invalid Content-Disposition parameter {param:?}, control characters are not allowed"
+            ));
+        }
+    }
+    let mut value = kind.to_string();
+    if let Some(name) = name {
+        let _ = write!(value, "; name={}", quote(name));
+    }
+    if let Some(filename) = filename {
+        if filename.is_ascii() {
+            let _ = write!(value, "; filename={}", quote(filename));
+        } else {
+            let fallback = filename
+                .chars()
+                .map(|c| if c.is_ascii() { c } else { '_' })
+                .collect::<String>();
+            let _ = write!(
+                value,
+                "; filename={}; filename*=UTF-8''{}",
+                quote(&fallback),
+                utf8_percent_encode(filename, ATTR_CHAR)
+            );
+        }
+    }
+    Ok(value)
+}
+
+pub fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Parse the value of a Content-Disposition header.
+///
+/// `filename*` and `name*` take precedence over `filename` and `name`.
+pub fn parse(value: &[u8]) -> Result<ContentDisposition> {
+    // browsers send non-ASCII filenames as raw UTF-8
+    let value = match std::str::from_utf8(value) {
+        Ok(v) => v.to_string(),
+        Err(_) => value.iter().map(|&b| b as char).collect(),
+    };
+
+    let (kind, mut rest) = match value.split_once(';') {
+        Some((kind, rest)) => (kind, rest),
+        None => (value.as_str(), ""),
+    };
+    let mut disposition = ContentDisposition {
+        kind: kind.trim().to_ascii_lowercase(),
+        ..Default::default()
+    };
+    let (mut name, mut filename) = (None, None);
+
+    loop {
+        rest = rest.trim_start_matches([' ', '\t', ';']);
+        if rest.is_empty() {
+            break;
+        }
+        let (key, after_key) = rest
+            .split_once('=')
+            .ok_or_else(|| anyhow!("invalid Content-Disposition parameter: {rest}"))?;
+        let key = key.trim().to_ascii_lowercase();
+        let after_key = after_key.trim_start();
+
+        let value;
+        (value, rest) = match after_key.strip_prefix('"') {
+            Some(quoted) => unquote(quoted)?,
+            None => match after_key.split_once(';') {
+                Some((token, rest)) => (token.trim_end().to_string(), rest),
+                None => (after_key.trim_end().to_string(), ""),
+            },
+        };
+
+        match key.as_str() {
+            "name" => name = name.or(Some(value)),
+            "filename" => filename = filename.or(Some(value)),
+            "name*" => disposition.name = Some(decode_ext_value(&value)?),
+            "filename*" => disposition.filename = Some(decode_ext_value(&value)?),
+            _ => {}
+        }
+    }
+
+    disposition.name = disposition.name.or(name);
+    disposition.filename = disposition.filename.or(filename);
+    for value in [&disposition.name, &disposition.filename]
+        .into_iter()
+        .flatten()
+    {
+        if value.contains(['\r', '\n', '\0']) {
+            return Err(anyhow!(
+                "invalid Content-Disposition parameter, control characters are not allowed"
+            ));
+        }
+    }
+    Ok(disposition)
+}
+
+// Parse the rest of a quoted string, returning the unescaped value and the remaining input.
+fn unquote(s: &str) -> Result<(String, &str)> {
+    let mut value = String::new();
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some((_, c)) => value.push(c),
+                None => break,
+            },
+            '"' => return Ok((value, &s[i + 1..])),
+            c => value.push(c),
+        }
+    }
+    Err(anyhow!("unterminated quoted string in Content-Disposition"))
+}
+
+// Decode an RFC 5987 ext-value, e.g. `UTF-8''%e2%82%ac%20rates`.
+fn decode_ext_value(value: &str) -> Result<String> {
+    let mut parts = value.splitn(3, '\'');
+    let (charset, encoded) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(charset), Some(_language), Some(encoded)) => (charset, encoded),
+        _ => return Err(anyhow!("invalid extended parameter value: {value}")),
+    };
+    let decoded = percent_decode_str(encoded);
+    if charset.eq_ignore_ascii_case("utf-8") {
+        Ok(decoded.decode_utf8()?.into_owned())
+    } else if charset.eq_ignore_ascii_case("iso-8859-1") {
+        Ok(decoded.map(|b| b as char).collect())
+    } else {
+        Err(anyhow!(
+            "unsupported charset in extended parameter: {charset}"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() -> Result<()> {
+        assert_eq!(
+            format("form-data", Some("field"), None)?,
+            r#"form-data; name="field""#
+        );
+        assert_eq!(
+            format("form-data", Some("my \"field\""), Some("a\\b.txt"))?,
+            r#"form-data; name="my \"field\""; filename="a\\b.txt""#
+        );
+        assert_eq!(
+            format("form-data", Some("file"), Some("€ rates.txt"))?,
+            r#"form-data; name="file"; filename="_ rates.txt"; filename*=UTF-8''%E2%82%AC%20rates.txt"#
+        );
+        assert_eq!(
+            format("attachment", None, Some("a.txt"))?,
+            r#"attachment; filename="a.txt""#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_invalid() {
+        assert!(format("form-data", Some("field\r\nX-Injected: 1"), None).is_err());
+        assert!(format("form-data", Some("file"), Some("file.txt\n")).is_err());
+        assert!(format("attachment", None, Some("a\0.txt")).is_err());
+    }
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        assert_eq!(
+            parse(br#"form-data; name=field"#)?,
+            ContentDisposition {
+                kind: "form-data".into(),
+                name: Some("field".into()),
+                filename: None,
+            }
+        );
+        assert_eq!(
+            parse(br#"form-data; name="my \"field\"; x"; filename="a\\b.txt""#)?,
+            ContentDisposition {
+                kind: "form-data".into(),
+                name: Some(r#"my "field"; x"#.into()),
+                filename: Some(r"a\b.txt".into()),
+            }
+        );
+        assert_eq!(
+            parse(
+                br#"Form-Data; filename*=UTF-8''%E2%82%AC%20rates.txt; name="file"; filename="_ rates.txt""#
+            )?
+            .filename,
+            Some("€ rates.txt".into())
+        );
+        assert_eq!(
+            parse("form-data; name=\"file\"; filename=\"€.txt\"".as_bytes())?.filename,
+            Some("€.txt".into())
+        );
+        assert_eq!(
+            parse(b"attachment; filename*=iso-8859-1'en'%A3.txt")?.filename,
+            Some("£.txt".into())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(parse(br#"form-data; name="unterminated"#).is_err());
+        assert!(parse(br#"form-data; name"#).is_err());
+        assert!(parse(b"form-data; name*=UTF-8''a%0D%0Ab").is_err());
+        assert!(parse(b"form-data; filename*=koi8-r''abc").is_err());
+    }
+
+    #[test]
+    fn test_roundtrip() -> Result<()> {
+        for (name, filename) in [("a \"b\" \\c", "d;e=f.txt"), ("名前", "ファイル.txt")] {
+            let disposition = parse(format("form-data", Some(name), Some(filename))?.as_bytes())?;
+            assert_eq!(disposition.name.as_deref(), Some(name));
+            assert_eq!(disposition.filename.as_deref(), Some(filename));
+        }
+        Ok(())
+    }
+}
@@ -1,18 +1,54 @@
+mod config;
 mod constants;
+mod disposition;
 pub(crate) mod parser;
 
-use crate::header::{HeaderMap, HeaderValue, IntoHeaderName, CONTENT_DISPOSITION, CONTENT_TYPE};
+pub use config::{MultipartConfig, MultipartError};
+pub use parser::{Field, MultipartReader};
 
-use anyhow::{Error, Result};
+use crate::{
+    common::de::{self, Value},
+    header::{HeaderMap, HeaderValue, IntoHeaderName, CONTENT_DISPOSITION, CONTENT_TYPE},
+};
+
+use anyhow::{anyhow, Error, Result};
 use mime::Mime;
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use serde::{
+    de::{DeserializeOwned, IgnoredAny, MapAccess, Visitor},
+    Deserialize, Deserializer,
+};
+use std::collections::VecDeque;
+use std::fmt;
 use std::fs::File;
-use std::io::Read;
+use std::io::{self, Cursor, Read};
 use std::path::Path;
 
+/// A multipart body, `multipart/form-data` by default.
+///
+/// Other subtypes such as `mixed` or `related` can be created with [`Form::with_subtype`]:
+///
+/// ```
+/// # use anyhow::Result;
+/// # use waki::multipart::{Form, Part};
+/// # fn run() -> Result<()> {
+/// let related = Form::with_subtype("related")
+///     .param("type", "application/json")
+///     .part(Part::new("", r#"{"name": "photo"}"#).mime(mime::APPLICATION_JSON))
+///     .part(Part::new("", vec![0x89, 0x50, 0x4e, 0x47]).mime(mime::IMAGE_PNG));
+///
+/// // Multiparts can be nested
+/// let mixed = Form::with_subtype("mixed")
+///     .part(Part::new("", "plain text").mime(mime::TEXT_PLAIN))
+///     .part(Part::multipart("", related)?);
+/// # Ok(())
+/// # }
+/// ```
 pub struct Form {
-    parts: Vec<Part>,
+    parts: Vec<(Part, Option<LazyValue>)>,
     boundary: String,
+    subtype: String,
+    params: Vec<(String, String)>,
 }
 
 impl Default for Form {
@@ -23,80 +59,258 @@ impl Default for Form {
 
 impl Form {
     pub fn new() -> Self {
+        Self::with_subtype(FORM_DATA)
+    }
+
+    /// Create a multipart body of another subtype, e.g. `mixed`, `related` or `byteranges`.
+    ///
+    /// Only `form-data` parts are sent with a Content-Disposition header containing their key,
+    /// parts of other subtypes are identified by their headers and only have a Content-Disposition
+    /// header if they have a filename.
+    pub fn with_subtype<S: Into<String>>(subtype: S) -> Self {
         Self {
             parts: vec![],
             boundary: format!("--FormBoundary{}", generate_random_string(10)),
+            subtype: subtype.into(),
+            params: vec![],
         }
     }
 
-    pub(crate) fn boundary(&self) -> &str {
+    /// Add a parameter to the Content-Type, e.g. `type` or `start` of `multipart/related`.
+    pub fn param<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.params.push((key.into(), value.into()));
+        self
+    }
+
+    /// Get the boundary separating the parts.
+    #[inline]
+    pub fn boundary(&self) -> &str {
         &self.boundary
     }
 
+    /// Get the value of the Content-Type header, including the boundary.
+    pub fn content_type(&self) -> String {
+        let mut content_type = format!("multipart/{}; boundary={}", self.subtype, self.boundary);
+        for (key, value) in &self.params {
+            content_type.push_str(&format!("; {}={}", key, disposition::quote(value)));
+        }
+        content_type
+    }
+
     pub fn text<S, V>(mut self, key: S, value: V) -> Self
     where
         S: Into<String>,
         V: Into<Vec<u8>>,
     {
-        self.parts.push(Part::new(key, value));
+        self.parts.push((Part::new(key, value), None));
         self
     }
 
-    pub fn file<S, P>(mut self, key: S, path: P) -> Result<Self>
+    /// Add a file part, the file is read when the form is sent.
+    ///
+    /// The MIME type is guessed from the file extension.
+    pub fn file<S, P>(self, key: S, path: P) -> Result<Self>
     where
         S: Into<String>,
         P: AsRef<Path>,
     {
-        self.parts.push(Part::file(key, path)?);
-        Ok(self)
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        let part = file_part(key, path, vec![]);
+        Ok(self.part_reader(part, file, Some(len)))
     }
 
     pub fn part(mut self, part: Part) -> Self {
-        self.parts.push(part);
+        self.parts.push((part, None));
+        self
+    }
+
+    /// Add a part whose data is read from the reader when the form is sent, the value of the
+    /// part is ignored.
+    ///
+    /// If the length is known, the form is sent with a Content-Length header, otherwise
+    /// it is sent with chunked transfer encoding. A known length is enforced: the data past it
+    /// is not sent, and a reader ending early fails the form with `UnexpectedEof`.
+    ///
+    /// ```
+    /// # use std::io::Read;
+    /// # use waki::multipart::{Form, Part};
+    /// let data = std::io::repeat(b'a').take(1024);
+    /// let form = Form::new().part_reader(
+    ///     Part::new("data", vec![]).filename("data.txt"),
+    ///     data,
+    ///     Some(1024),
+    /// );
+    /// ```
+    pub fn part_reader<R: Read + 'static>(
+        mut self,
+        part: Part,
+        reader: R,
+        len: Option<u64>,
+    ) -> Self {
+        let lazy = LazyValue {
+            reader: Box::new(reader),
+            len,
+        };
+        self.parts.push((part, Some(lazy)));
         self
     }
 
+    /// Get the size of the encoded form, or None if the size of a part is unknown or the form is
+    /// invalid.
+    pub fn content_length(&self) -> Option<u64> {
+        let mut len = self.closing_boundary().len() as u64;
+        for (part, lazy) in &self.parts {
+            len += (self.part_headers(part).ok()?.len() + constants::CRLF.len()) as u64;
+            len += match lazy {
+                Some(lazy) => lazy.len?,
+                None => part.value.len() as u64,
+            };
+        }
+        Some(len)
+    }
+
+    /// Encode the form into a reader, the data of lazy parts is read as the reader is read.
+    ///
+    /// Fails if the name or filename of a part contains control characters.
+    pub fn into_reader(mut self) -> Result<impl Read> {
+        let mut readers: VecDeque<Box<dyn Read>> = VecDeque::new();
+        for (part, lazy) in std::mem::take(&mut self.parts) {
+            readers.push_back(Box::new(Cursor::new(self.part_headers(&part)?)));
+            readers.push_back(match lazy {
+                Some(LazyValue {
+                    reader,
+                    len: Some(len),
+                }) => Box::new(ExactReader(reader.take(len))),
+                Some(lazy) => lazy.reader,
+                None => Box::new(Cursor::new(part.value)),
+            });
+            readers.push_back(Box::new(constants::CRLF.as_bytes()));
+        }
+        readers.push_back(Box::new(Cursor::new(self.closing_boundary())));
+        Ok(FormReader { readers })
+    }
+
+    /// Encode the form.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name or filename of a part contains control characters, or if the data of a
+    /// lazy part fails to be read. Use [`Form::try_build`] to handle these errors.
     pub fn build(self) -> Vec<u8> {
+        self.try_build()
+            .expect("failed to encode the multipart form")
+    }
+
+    /// Encode the form, reading the data of all lazy parts.
+    ///
+    /// Fails if the name or filename of a part contains control characters, or if the data of a
+    /// lazy part fails to be read.
+    pub fn try_build(self) -> Result<Vec<u8>> {
         let mut buf = vec![];
-        for part in self.parts {
+        self.into_reader()?.read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn part_headers(&self, part: &Part) -> Result<Vec<u8>> {
+        let mut buf = format!("{}{}", constants::BOUNDARY_EXT, self.boundary).into_bytes();
+        let filename = part.filename.as_deref();
+        let disposition = if self.subtype == FORM_DATA {
+            Some(disposition::format(FORM_DATA, Some(&part.key), filename)?)
+        } else {
+            filename
+                .map(|filename| disposition::format("attachment", None, Some(filename)))
+                .transpose()?
+        };
+        if let Some(disposition) = disposition {
             buf.extend_from_slice(
                 format!(
-                    "{}{}{}{}: form-data; name={}",
-                    constants::BOUNDARY_EXT,
-                    self.boundary,
+                    "{}{}: {}",
                     constants::CRLF,
                     CONTENT_DISPOSITION,
-                    part.key
+                    disposition
                 )
                 .as_bytes(),
             );
-            if let Some(filename) = part.filename {
-                buf.extend_from_slice(format!("; filename=\"{}\"", filename).as_bytes());
-            }
-            if let Some(mime) = part.mime {
-                buf.extend_from_slice(
-                    format!("{}{}: {}", constants::CRLF, CONTENT_TYPE, mime).as_bytes(),
-                );
-            }
-            for (k, v) in part.headers.iter() {
-                buf.extend_from_slice(format!("{}{}: ", constants::CRLF, k).as_bytes());
-                buf.extend_from_slice(v.as_bytes());
+        }
+        if let Some(mime) = &part.mime {
+            buf.extend_from_slice(
+                format!("{}{}: {}", constants::CRLF, CONTENT_TYPE, mime).as_bytes(),
+            );
+        }
+        for (k, v) in part.headers.iter() {
+            buf.extend_from_slice(format!("{}{}: ", constants::CRLF, k).as_bytes());
+            buf.extend_from_slice(v.as_bytes());
+        }
+        buf.extend_from_slice(constants::CRLF_CRLF.as_bytes());
+        Ok(buf)
+    }
+
+    fn closing_boundary(&self) -> Vec<u8> {
+        format!(
+            "{}{}{}",
+            constants::BOUNDARY_EXT,
+            self.boundary,
+            constants::BOUNDARY_EXT,
+        )
+        .into_bytes()
+    }
+}
+
+// The data of a part read when the form is sent, the value of the part is unused.
+struct LazyValue {
+    reader: Box<dyn Read>,
+    len: Option<u64>,
+}
+
+// Reads the encoded parts one after another.
+struct FormReader {
+    readers: VecDeque<Box<dyn Read>>,
+}
+
+impl Read for FormReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while let Some(reader) = self.readers.front_mut() {
+            let n = reader.read(buf)?;
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
             }
+            self.readers.pop_front();
+        }
+        Ok(0)
+    }
+}
+
+// Reads exactly the declared length of a lazy part, which the Content-Length relies on.
+struct ExactReader(io::Take<Box<dyn Read>>);
 
-            buf.extend_from_slice(constants::CRLF_CRLF.as_bytes());
-            buf.extend_from_slice(&part.value);
-            buf.extend_from_slice(constants::CRLF.as_bytes());
+impl Read for ExactReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.0.read(buf)?;
+        if n == 0 && !buf.is_empty() && self.0.limit() > 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
         }
-        buf.extend_from_slice(
-            format!(
-                "{}{}{}",
-                constants::BOUNDARY_EXT,
-                self.boundary,
-                constants::BOUNDARY_EXT,
-            )
-            .as_bytes(),
-        );
-        buf
+        Ok(n)
+    }
+}
+
+const FORM_DATA: &str = "form-data";
+
+// A part named after the file, with the MIME type guessed from its extension.
+fn file_part<S: Into<String>>(key: S, path: &Path, value: Vec<u8>) -> Part {
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+    let part = Part::new(key, value).mime(mime);
+    match path
+        .file_name()
+        .map(|filename| filename.to_string_lossy().to_string())
+    {
+        Some(name) => part.filename(name),
+        None => part,
     }
 }
 
@@ -108,6 +322,187 @@ fn generate_random_string(length: usize) -> String {
         .collect()
 }
 
+/// The parts of a multipart body, in the order they were sent.
+///
+/// Multiple parts can have the same name, e.g. when uploading several files with
+/// `<input type="file" multiple>`.
+#[derive(Default)]
+pub struct Multipart {
+    parts: Vec<Part>,
+}
+
+impl Multipart {
+    /// Get the first part with the name.
+    pub fn get(&self, name: &str) -> Option<&Part> {
+        self.parts.iter().find(|part| part.key == name)
+    }
+
+    /// Get all parts with the name.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Part> {
+        self.parts.iter().filter(move |part| part.key == name)
+    }
+
+    /// Iterate over all parts.
+    #[inline]
+    pub fn iter(&self) -> std::slice::Iter<'_, Part> {
+        self.parts.iter()
+    }
+
+    /// Iterate over the parts that are files, i.e. that have a filename.
+    pub fn files(&self) -> impl Iterator<Item = &Part> {
+        self.parts.iter().filter(|part| part.filename.is_some())
+    }
+
+    /// Get the number of parts.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.parts.len()
+    }
+
+    /// Whether there are no parts.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    /// Deserialize the parts into `T`, the parts are mapped to the fields by name.
+    ///
+    /// Text fields are parsed into numbers, booleans etc., repeated fields can be collected into
+    /// a `Vec`, and files can be captured as [`UploadedFile`].
+    ///
+    /// ```
+    /// # use anyhow::Result;
+    /// # use serde::Deserialize;
+    /// # use waki::multipart::{Multipart, UploadedFile};
+    /// #[derive(Deserialize)]
+    /// struct Upload {
+    ///     title: String,
+    ///     tags: Vec<String>,
+    ///     public: Option<bool>,
+    ///     files: Vec<UploadedFile>,
+    /// }
+    ///
+    /// # fn run(multipart: Multipart) -> Result<()> {
+    /// let upload: Upload = multipart.deserialize()?;
+    /// for file in upload.files {
+    ///     println!("{:?}: {} bytes", file.filename, file.data.len());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn deserialize<T: DeserializeOwned>(self) -> Result<T> {
+        Ok(de::from_pairs(self.parts.into_iter().map(|part| {
+            (part.key.clone(), Value::File(Box::new(part)))
+        }))?)
+    }
+}
+
+pub(crate) const UPLOADED_FILE: &str = "$waki::multipart::UploadedFile";
+
+/// A file of a multipart form, see [`Multipart::deserialize`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UploadedFile {
+    pub filename: Option<String>,
+    pub mime: Option<Mime>,
+    pub data: Vec<u8>,
+}
+
+impl<'de> Deserialize<'de> for UploadedFile {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FileVisitor;
+
+        impl<'de> Visitor<'de> for FileVisitor {
+            type Value = UploadedFile;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a file")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut file = UploadedFile::default();
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "filename" => file.filename = Some(map.next_value()?),
+                        "content_type" => {
+                            let mime = map.next_value::<String>()?;
+                            file.mime = Some(mime.parse().map_err(serde::de::Error::custom)?);
+                        }
+                        "data" => file.data = map.next_value::<ByteBuf>()?.0,
+                        _ => {
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+                Ok(file)
+            }
+        }
+
+        deserializer.deserialize_struct(
+            UPLOADED_FILE,
+            &["filename", "content_type", "data"],
+            FileVisitor,
+        )
+    }
+}
+
+struct ByteBuf(Vec<u8>);
+
+impl<'de> Deserialize<'de> for ByteBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ByteBufVisitor;
+
+        impl Visitor<'_> for ByteBufVisitor {
+            type Value = ByteBuf;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("bytes")
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> {
+                Ok(ByteBuf(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+                Ok(ByteBuf(v))
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(ByteBuf(v.as_bytes().to_vec()))
+            }
+        }
+
+        deserializer.deserialize_byte_buf(ByteBufVisitor)
+    }
+}
+
+impl FromIterator<Part> for Multipart {
+    fn from_iter<I: IntoIterator<Item = Part>>(iter: I) -> Self {
+        Self {
+            parts: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl IntoIterator for Multipart {
+    type Item = Part;
+    type IntoIter = std::vec::IntoIter<Part>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.parts.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Multipart {
+    type Item = &'a Part;
+    type IntoIter = std::slice::Iter<'a, Part>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.parts.iter()
+    }
+}
+
 pub struct Part {
     pub key: String,
     pub value: Vec<u8>,
@@ -131,25 +526,36 @@ impl Part {
         }
     }
 
+    /// Create a part containing a nested multipart body, the data of its lazy parts is read
+    /// immediately.
+    pub fn multipart<S: Into<String>>(key: S, form: Form) -> Result<Self> {
+        let mime = form.content_type().parse()?;
+        Ok(Part::new(key, form.try_build()?).mime(mime))
+    }
+
+    /// Create a part from a file.
+    ///
+    /// The MIME type is guessed from the file extension.
     pub fn file<S, P>(key: S, path: P) -> Result<Self>
     where
         S: Into<String>,
         P: AsRef<Path>,
     {
         let path = path.as_ref();
-        let mime = mime_guess::from_path(path).first_or_octet_stream();
         let mut file = File::open(path)?;
         let mut buffer = vec![];
         file.read_to_end(&mut buffer)?;
-        let part = Part::new(key, buffer).mime(mime);
-
-        match path
-            .file_name()
-            .map(|filename| filename.to_string_lossy().to_string())
-        {
-            Some(name) => Ok(part.filename(name)),
-            None => Ok(part),
-        }
+        Ok(file_part(key, path, buffer))
+    }
+
+    /// Parse the value as a nested multipart body, using the boundary of the MIME type.
+    pub fn parse_multipart(&self) -> Result<Multipart> {
+        let boundary = self
+            .mime
+            .as_ref()
+            .and_then(|mime| mime.get_param(mime::BOUNDARY))
+            .ok_or_else(|| anyhow!("unable to find the boundary of the nested multipart"))?;
+        MultipartReader::new(self.value.as_slice(), boundary.as_str()).into_multipart()
     }
 
     pub fn mime(mut self, mime: Mime) -> Self {
@@ -1,116 +1,459 @@
 use crate::{
     header::{HeaderMap, HeaderName, HeaderValue, CONTENT_DISPOSITION, CONTENT_TYPE},
-    multipart::{constants, Part},
+    multipart::{constants, disposition, Multipart, MultipartConfig, MultipartError, Part},
 };
 
-use anyhow::{anyhow, Result};
-use bytes::{Buf, Bytes, BytesMut};
+use anyhow::{anyhow, Error, Result};
 use httparse::Status;
-use std::collections::HashMap;
+use mime::Mime;
+use std::collections::HashSet;
+use std::io::{self, Read};
 
-struct Buffer {
-    buf: BytesMut,
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    // before the first boundary
+    Preamble,
+    // reading the data of a field
+    FieldData,
+    // right after a boundary, either the headers of the next field or the end of the body follow
+    Boundary,
+    Done,
 }
 
-impl Buffer {
-    fn new(data: &[u8]) -> Self {
-        Self { buf: data.into() }
-    }
+/// A streaming multipart parser, yielding the fields one at a time.
+///
+/// Only the data of the current field is buffered, the body is read as the fields are read.
+///
+/// ```
+/// # use anyhow::Result;
+/// # use std::io::Read;
+/// # use waki::{ErrorCode, Request, Response};
+/// # fn run(req: Request) -> Result<()> {
+/// let mut multipart = req.multipart_reader()?;
+/// while let Some(mut field) = multipart.next_field()? {
+///     if field.filename().is_some() {
+///         let mut file = std::fs::File::create(field.name())?;
+///         std::io::copy(&mut field, &mut file)?;
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Limits can be enforced with a [`MultipartConfig`], violations are reported as
+/// [`MultipartError`].
+pub struct MultipartReader<R> {
+    reader: R,
+    buf: Vec<u8>,
+    // the start of the unprocessed data in `buf`
+    pos: usize,
+    eof: bool,
+    // `--boundary`
+    dash_boundary: Vec<u8>,
+    // `\r\n--boundary`
+    delimiter: Vec<u8>,
+    state: State,
+    config: MultipartConfig,
+    // the limit applied when the config has no `max_total_bytes`
+    body_limit: Option<u64>,
+    // the number of bytes read from the body
+    total: u64,
+    parts: usize,
+    field: Option<FieldLimit>,
+    seen: HashSet<String>,
+}
 
-    fn peek_exact(&mut self, size: usize) -> Option<&[u8]> {
-        self.buf.get(..size)
-    }
+// The size limit of the current field.
+struct FieldLimit {
+    name: String,
+    file: bool,
+    size: u64,
+    limit: Option<u64>,
+}
 
-    fn read_until(&mut self, pattern: &[u8]) -> Option<Bytes> {
-        memchr::memmem::find(&self.buf, pattern)
-            .map(|idx| self.buf.split_to(idx + pattern.len()).freeze())
+impl<R: Read> MultipartReader<R> {
+    pub fn new(reader: R, boundary: &str) -> Self {
+        let dash_boundary = format!("{}{}", constants::BOUNDARY_EXT, boundary).into_bytes();
+        Self {
+            reader,
+            buf: Vec::new(),
+            pos: 0,
+            eof: false,
+            delimiter: [constants::CRLF.as_bytes(), &dash_boundary].concat(),
+            dash_boundary,
+            state: State::Preamble,
+            config: MultipartConfig::default(),
+            body_limit: None,
+            total: 0,
+            parts: 0,
+            field: None,
+            seen: HashSet::new(),
+        }
     }
 
-    fn read_to(&mut self, pattern: &[u8]) -> Option<Bytes> {
-        memchr::memmem::find(&self.buf, pattern).map(|idx| self.buf.split_to(idx).freeze())
+    /// Set the limits and validation rules enforced while parsing.
+    pub fn config(mut self, config: MultipartConfig) -> Self {
+        self.config = config;
+        self
     }
 
-    fn advance(&mut self, n: usize) {
-        self.buf.advance(n)
+    pub(crate) fn body_limit(mut self, limit: u64) -> Self {
+        self.body_limit = Some(limit);
+        self
     }
-}
 
-pub fn parse(body: &[u8], boundary: &str) -> Result<HashMap<String, Part>> {
-    let mut buffer = Buffer::new(body);
-    let boundary = format!("{}{}", constants::BOUNDARY_EXT, boundary);
-
-    // Finding the first boundary
-    if buffer
-        .read_until(format!("{}{}", boundary, constants::CRLF).as_bytes())
-        .is_none()
-    {
-        return Err(anyhow!("incomplete multipart data, missing boundary"));
-    };
+    /// Read all the remaining fields into a [`Multipart`].
+    pub fn into_multipart(mut self) -> Result<Multipart> {
+        let mut parts = vec![];
+        while let Some(field) = self.next_field()? {
+            parts.push(field.into_part()?);
+        }
+        Ok(Multipart { parts })
+    }
 
-    let mut parts = HashMap::new();
+    /// Get the next field, returning None when all fields have been read.
+    ///
+    /// The unread data of the previous field is skipped.
+    pub fn next_field(&mut self) -> Result<Option<Field<'_, R>>> {
+        loop {
+            match self.state {
+                State::Done => return Ok(None),
+                State::Preamble => {
+                    // Finding the first boundary, the preamble is discarded as it is scanned
+                    let mut skipped = 0;
+                    let idx = loop {
+                        let found = memchr::memmem::find(self.remaining(), &self.dash_boundary);
+                        // keep the bytes that could be the beginning of the boundary
+                        let len = match found {
+                            Some(idx) => idx,
+                            None => self
+                                .remaining()
+                                .len()
+                                .saturating_sub(self.dash_boundary.len() - 1),
+                        };
+                        if skipped + len > constants::MAX_PREAMBLE_SIZE {
+                            return Err(anyhow!(
+                                "invalid multipart data, the preamble exceeds {} bytes",
+                                constants::MAX_PREAMBLE_SIZE
+                            ));
+                        }
+                        if let Some(idx) = found {
+                            break idx;
+                        }
+                        self.pos += len;
+                        skipped += len;
+                        if !self.fill().map_err(from_io)? {
+                            return Err(anyhow!("incomplete multipart data, missing boundary"));
+                        }
+                    };
+                    self.pos += idx + self.dash_boundary.len();
+                    self.state = State::Boundary;
+                }
+                State::FieldData => {
+                    let mut buf = [0; 8 * 1024];
+                    while self.read_field_data(&mut buf).map_err(from_io)? > 0 {}
+                }
+                State::Boundary => {
+                    // Determine end of stream
+                    if !self
+                        .fill_to(constants::BOUNDARY_EXT.len())
+                        .map_err(from_io)?
+                    {
+                        return Err(anyhow!("incomplete multipart data"));
+                    }
+                    if self
+                        .remaining()
+                        .starts_with(constants::BOUNDARY_EXT.as_bytes())
+                    {
+                        self.state = State::Done;
+                        if let Some(name) = self
+                            .config
+                            .required
+                            .iter()
+                            .find(|n| !self.seen.contains(*n))
+                        {
+                            return Err(MultipartError::MissingField { name: name.clone() }.into());
+                        }
+                        return Ok(None);
+                    }
+                    // discard \r\n.
+                    if !self.remaining().starts_with(constants::CRLF.as_bytes()) {
+                        return Err(anyhow!(
+                            "invalid multipart data, missing CRLF after boundary"
+                        ));
+                    }
+                    self.pos += constants::CRLF.len();
+                    if let Some(limit) = self.config.max_parts {
+                        if self.parts >= limit {
+                            return Err(MultipartError::TooManyParts { limit }.into());
+                        }
+                    }
+                    self.parts += 1;
+                    return self.read_headers().map(Some);
+                }
+            }
+        }
+    }
 
-    loop {
+    fn read_headers(&mut self) -> Result<Field<'_, R>> {
         // Finding headers
-        let header_bytes = match buffer.read_until(constants::CRLF_CRLF.as_bytes()) {
-            Some(bytes) => bytes,
-            None => return Err(anyhow!("incomplete multipart data, missing headers")),
+        let header_bytes = if self.fill_to(constants::CRLF.len()).map_err(from_io)?
+            && self.remaining().starts_with(constants::CRLF.as_bytes())
+        {
+            // a field without headers
+            constants::CRLF.as_bytes().to_vec()
+        } else {
+            let idx = self
+                .find(constants::CRLF_CRLF.as_bytes(), constants::MAX_HEADERS_SIZE)
+                .map_err(from_io)?
+                .ok_or_else(|| anyhow!("incomplete multipart data, missing headers"))?;
+            self.remaining()[..idx + constants::CRLF_CRLF.len()].to_vec()
         };
+        self.pos += header_bytes.len();
 
-        let mut part = Part::new("", vec![]);
         let mut headers = [httparse::EMPTY_HEADER; constants::MAX_HEADERS];
-        part.headers = match httparse::parse_headers(&header_bytes, &mut headers)? {
+        let field = match httparse::parse_headers(&header_bytes, &mut headers)? {
             Status::Complete((_, raw_headers)) => {
-                let mut headers_map = HeaderMap::with_capacity(raw_headers.len());
+                let mut field = Field {
+                    name: String::new(),
+                    filename: None,
+                    mime: None,
+                    headers: HeaderMap::with_capacity(raw_headers.len()),
+                    reader: self,
+                };
                 for header in raw_headers {
                     let (k, v) = (
                         HeaderName::try_from(header.name)?,
                         HeaderValue::try_from(header.value)?,
                     );
                     if k == CONTENT_DISPOSITION {
-                        // can't parse it without a /
-                        let mime = format!("multipart/{}", v.to_str()?).parse::<mime::Mime>()?;
-                        part.key = match mime.get_param("name") {
-                            Some(name) => name.to_string(),
-                            None => {
+                        let disposition = disposition::parse(v.as_bytes())?;
+                        // only form-data parts are required to have a name
+                        field.name = match disposition.name {
+                            Some(name) => name,
+                            None if disposition.kind == "form-data" => {
                                 return Err(anyhow!(
                                     "missing name field in the Content-Disposition header"
                                 ))
                             }
+                            None => String::new(),
                         };
-                        part.filename = mime.get_param("filename").map(|v| v.to_string());
+                        field.filename = disposition.filename;
                     };
                     if k == CONTENT_TYPE {
-                        part.mime = Some(v.to_str()?.parse()?)
+                        field.mime = Some(v.to_str()?.parse()?)
                     }
-                    headers_map.insert(k, v);
+                    field.headers.append(k, v);
                 }
-                headers_map
+                field
             }
             Status::Partial => return Err(anyhow!("failed to parse field complete headers")),
         };
 
-        // Finding field data
-        part.value = match buffer.read_to(format!("{}{}", constants::CRLF, boundary).as_bytes()) {
-            Some(bytes) => bytes.to_vec(),
-            None => return Err(anyhow!("incomplete multipart data, missing field data")),
+        let config = &field.reader.config;
+        if !config.is_allowed(&field.name, field.mime.as_ref()) {
+            return Err(MultipartError::UnsupportedMediaType {
+                name: field.name,
+                mime: field
+                    .mime
+                    .map_or_else(|| mime::TEXT_PLAIN.to_string(), |m| m.to_string()),
+            }
+            .into());
+        }
+        let file = field.filename.is_some();
+        let limit = if file {
+            config.max_file_bytes
+        } else {
+            config.max_field_bytes
         };
+        if !config.required.is_empty() {
+            field.reader.seen.insert(field.name.clone());
+        }
+        field.reader.field = Some(FieldLimit {
+            name: field.name.clone(),
+            file,
+            size: 0,
+            limit,
+        });
+        field.reader.state = State::FieldData;
+        Ok(field)
+    }
 
-        // Determine end of stream
-        if buffer.read_until(boundary.as_bytes()).is_none() {
-            return Err(anyhow!("incomplete multipart data, missing boundary"));
+    fn read_field_data(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.state != State::FieldData || out.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let available = self.remaining();
+            let len = match memchr::memmem::find(available, &self.delimiter) {
+                Some(0) => {
+                    self.pos += self.delimiter.len();
+                    self.state = State::Boundary;
+                    return Ok(0);
+                }
+                Some(idx) => idx,
+                // keep the bytes that could be the beginning of the delimiter
+                None => available.len().saturating_sub(self.delimiter.len() - 1),
+            };
+            if len > 0 {
+                let len = len.min(out.len());
+                out[..len].copy_from_slice(&available[..len]);
+                self.pos += len;
+                self.count_field_data(len)?;
+                return Ok(len);
+            }
+            if !self.fill()? {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "incomplete multipart data, missing boundary",
+                ));
+            }
+        }
+    }
+
+    fn count_field_data(&mut self, len: usize) -> Result<(), MultipartError> {
+        let Some(field) = &mut self.field else {
+            return Ok(());
         };
-        let next_bytes = match buffer.peek_exact(constants::BOUNDARY_EXT.len()) {
-            Some(bytes) => bytes,
-            None => return Err(anyhow!("incomplete multipart data")),
+        field.size += len as u64;
+        match field.limit {
+            Some(limit) if field.size > limit => {
+                let name = field.name.clone();
+                Err(if field.file {
+                    MultipartError::FileTooLarge { name, limit }
+                } else {
+                    MultipartError::FieldTooLarge { name, limit }
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    #[inline]
+    fn remaining(&self) -> &[u8] {
+        &self.buf[self.pos..]
+    }
+
+    // Read more data into the buffer, returning false at the end of the body.
+    fn fill(&mut self) -> io::Result<bool> {
+        if self.eof {
+            return Ok(false);
+        }
+        if self.pos > 0 {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+        }
+        let len = self.buf.len();
+        self.buf.resize(len + 64 * 1024, 0);
+        let n = loop {
+            match self.reader.read(&mut self.buf[len..]) {
+                Ok(n) => break n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.buf.truncate(len);
+                    return Err(e);
+                }
+            }
         };
+        self.buf.truncate(len + n);
+        if n == 0 {
+            self.eof = true;
+        }
+        self.total += n as u64;
+        if let Some(limit) = self.config.max_total_bytes.or(self.body_limit) {
+            if self.total > limit {
+                return Err(MultipartError::BodyTooLarge { limit }.into());
+            }
+        }
+        Ok(n > 0)
+    }
 
-        parts.insert(part.key.clone(), part);
+    // Make sure at least `len` bytes are buffered, returning false if the body is shorter.
+    fn fill_to(&mut self, len: usize) -> io::Result<bool> {
+        while self.remaining().len() < len {
+            if !self.fill()? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
 
-        if next_bytes == constants::BOUNDARY_EXT.as_bytes() {
-            return Ok(parts);
+    // Find the pattern in the first `max` bytes of the unprocessed data, reading more data as needed.
+    fn find(&mut self, pattern: &[u8], max: usize) -> io::Result<Option<usize>> {
+        let mut from = 0;
+        loop {
+            if let Some(idx) = memchr::memmem::find(&self.remaining()[from..], pattern) {
+                return Ok(Some(from + idx).filter(|idx| *idx <= max));
+            }
+            from = self.remaining().len().saturating_sub(pattern.len() - 1);
+            if from > max {
+                return Ok(None);
+            }
+            if !self.fill()? {
+                return Ok(None);
+            }
         }
-        // discard \r\n.
-        buffer.advance(constants::CRLF.len());
+    }
+}
+
+/// A field of a multipart body, its data can be read through the [`Read`] implementation.
+pub struct Field<'a, R> {
+    name: String,
+    filename: Option<String>,
+    mime: Option<Mime>,
+    headers: HeaderMap,
+    reader: &'a mut MultipartReader<R>,
+}
+
+impl<R: Read> Field<'_, R> {
+    /// Get the name of the field.
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the filename of the field.
+    #[inline]
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_deref()
+    }
+
+    /// Get the MIME type of the field.
+    #[inline]
+    pub fn mime(&self) -> Option<&Mime> {
+        self.mime.as_ref()
+    }
+
+    /// Get the headers of the field.
+    #[inline]
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Read the rest of the field data into a [`Part`].
+    pub fn into_part(mut self) -> Result<Part> {
+        let mut value = vec![];
+        self.read_to_end(&mut value).map_err(from_io)?;
+        let mut part = Part::new(self.name, value);
+        part.filename = self.filename;
+        part.mime = self.mime;
+        part.headers = self.headers;
+        Ok(part)
+    }
+}
+
+impl<R: Read> Read for Field<'_, R> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read_field_data(buf)
+    }
+}
+
+// Unwrap the multipart errors from I/O errors, so they can be downcast.
+fn from_io(e: io::Error) -> Error {
+    match MultipartError::from_io(&e).cloned() {
+        Some(e) => e.into(),
+        None => e.into(),
     }
 }
 
@@ -118,6 +461,10 @@ pub fn parse(body: &[u8], boundary: &str) -> Result<HashMap<String, Part>> {
 mod tests {
     use super::*;
 
+    fn parse(body: &[u8], boundary: &str) -> Result<Multipart> {
+        MultipartReader::new(body, boundary).into_multipart()
+    }
+
     #[test]
     fn test_parse() -> Result<()> {
         let data = b"--boundary\r\nContent-Disposition: form-data; name=field1\r\n\r\nvalue1\r\n--boundary\r\nContent-Disposition: form-data; name=field2; filename=file.txt\r\nContent-Type: text/plain\r\n\r\nhello\r\n--boundary--";
@@ -138,4 +485,404 @@ mod tests {
         assert_eq!(field2.headers.len(), 2);
         Ok(())
     }
+
+    #[test]
+    fn test_parse_repeated() -> Result<()> {
+        let data = b"--boundary\r\nContent-Disposition: form-data; name=files; filename=a.txt\r\n\r\na\r\n--boundary\r\nContent-Disposition: form-data; name=text\r\n\r\nvalue\r\n--boundary\r\nContent-Disposition: form-data; name=files; filename=b.txt\r\n\r\nb\r\n--boundary--";
+
+        let parts = parse(data, "boundary")?;
+        assert_eq!(parts.len(), 3);
+        assert_eq!(
+            parts.iter().map(|p| p.key.as_str()).collect::<Vec<_>>(),
+            ["files", "text", "files"]
+        );
+        assert_eq!(parts.get("files").unwrap().value, b"a");
+        assert_eq!(
+            parts
+                .get_all("files")
+                .map(|p| p.filename.as_deref().unwrap())
+                .collect::<Vec<_>>(),
+            ["a.txt", "b.txt"]
+        );
+        assert_eq!(parts.files().count(), 2);
+        assert!(parts.get("missing").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_form() -> Result<()> {
+        let form = crate::multipart::Form::new()
+            .text("name \"quoted\"", "value")
+            .part(Part::new("file", "data").filename("résumé \"v2\".txt"));
+        let boundary = form.boundary().to_string();
+
+        let parts = parse(&form.build(), &boundary)?;
+        assert_eq!(parts.len(), 2);
+        let text = parts.iter().next().unwrap();
+        assert_eq!(text.key, "name \"quoted\"");
+        assert_eq!(text.headers.len(), 1);
+        let file = parts.get("file").unwrap();
+        assert_eq!(file.filename.as_deref(), Some("résumé \"v2\".txt"));
+        assert_eq!(file.value, b"data");
+
+        // control characters could inject headers
+        let form = crate::multipart::Form::new().text("name\r\nX-Injected: 1", "value");
+        assert!(form.try_build().is_err());
+        let form = crate::multipart::Form::with_subtype("mixed")
+            .part(Part::new("", "data").filename("a\r\n.txt"));
+        assert!(form.into_reader().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_form() {
+        let form = crate::multipart::Form::with_subtype("form\ndata");
+        assert!(crate::Response::builder().multipart(form).build().is_err());
+        let form = crate::multipart::Form::new().param("type", "a\r\nb");
+        assert!(crate::Response::builder().multipart(form).build().is_err());
+        let form = crate::multipart::Form::new().text("a\nb", "value");
+        assert!(crate::Response::builder().multipart(form).build().is_err());
+    }
+
+    #[test]
+    fn test_lazy_form() -> Result<()> {
+        let form = crate::multipart::Form::new()
+            .text("text", "value")
+            .part_reader(
+                Part::new("file", vec![]).filename("a.txt"),
+                io::repeat(b'a').take(100_000),
+                Some(100_000),
+            );
+        let boundary = form.boundary().to_string();
+        let len = form.content_length();
+
+        let data = form.try_build()?;
+        assert_eq!(len, Some(data.len() as u64));
+        let parts = parse(&data, &boundary)?;
+        assert_eq!(parts.get("text").unwrap().value, b"value");
+        assert_eq!(parts.get("file").unwrap().value, vec![b'a'; 100_000]);
+
+        let form = crate::multipart::Form::new().part_reader(
+            Part::new("data", vec![]),
+            &b"data"[..],
+            None,
+        );
+        assert_eq!(form.content_length(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lazy_form_len() -> Result<()> {
+        // a longer reader is cut at the declared length
+        let form = crate::multipart::Form::new()
+            .part_reader(Part::new("data", vec![]), &b"data"[..], Some(2))
+            .text("text", "value");
+        let boundary = form.boundary().to_string();
+        let len = form.content_length();
+        let data = form.try_build()?;
+        assert_eq!(len, Some(data.len() as u64));
+        let parts = parse(&data, &boundary)?;
+        assert_eq!(parts.get("data").unwrap().value, b"da");
+        assert_eq!(parts.get("text").unwrap().value, b"value");
+
+        // a shorter reader fails the form
+        let form = crate::multipart::Form::new().part_reader(
+            Part::new("data", vec![]),
+            &b"data"[..],
+            Some(10),
+        );
+        let err = form.try_build().unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<io::Error>().unwrap().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested_form() -> Result<()> {
+        let related = crate::multipart::Form::with_subtype("related")
+            .param("type", "application/json")
+            .part(Part::new("", "{}").mime(mime::APPLICATION_JSON))
+            .part(Part::new("ignored", "data").filename("a.bin"));
+        assert!(related
+            .content_type()
+            .ends_with(r#"; type="application/json""#));
+        let form = crate::multipart::Form::with_subtype("mixed")
+            .part(Part::new("", "text"))
+            .part(Part::multipart("", related)?);
+        let boundary = form.boundary().to_string();
+        let len = form.content_length();
+        let data = form.try_build()?;
+        assert_eq!(len, Some(data.len() as u64));
+
+        let parts = parse(&data, &boundary)?;
+        assert_eq!(parts.len(), 2);
+        let mut iter = parts.iter();
+        let text = iter.next().unwrap();
+        assert!(text.headers.is_empty());
+        assert_eq!(text.value, b"text");
+
+        let nested = iter.next().unwrap();
+        assert_eq!(nested.mime.as_ref().unwrap().subtype(), "related");
+        let nested = nested.parse_multipart()?;
+        let mut iter = nested.iter();
+        let json = iter.next().unwrap();
+        assert_eq!(json.mime, Some(mime::APPLICATION_JSON));
+        assert_eq!(json.value, b"{}");
+        let file = iter.next().unwrap();
+        assert_eq!(file.key, "");
+        assert_eq!(file.filename.as_deref(), Some("a.bin"));
+        assert_eq!(
+            file.headers.get(CONTENT_DISPOSITION).unwrap(),
+            r#"attachment; filename="a.bin""#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_byteranges() -> Result<()> {
+        let data = b"--boundary\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-4/20\r\n\r\nhello\r\n--boundary\r\nContent-Type: text/plain\r\nContent-Range: bytes 15-19/20\r\n\r\nworld\r\n--boundary--\r\n";
+
+        let parts = parse(data, "boundary")?;
+        assert_eq!(
+            parts
+                .iter()
+                .map(|p| (
+                    p.headers.get("Content-Range").unwrap().to_str().unwrap(),
+                    p.value.as_slice()
+                ))
+                .collect::<Vec<_>>(),
+            [
+                ("bytes 0-4/20", &b"hello"[..]),
+                ("bytes 15-19/20", &b"world"[..])
+            ]
+        );
+        Ok(())
+    }
+
+    // Yields the data one byte at a time, to exercise the buffering.
+    struct ByteReader<'a>(&'a [u8]);
+
+    impl Read for ByteReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.split_first() {
+                Some((b, rest)) if !buf.is_empty() => {
+                    buf[0] = *b;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn test_reader() -> Result<()> {
+        let data = b"preamble\r\n--boundary\r\nContent-Disposition: form-data; name=skipped\r\n\r\nvalue\r\n--boundar\r\n--boundary\r\n\r\n\r\n--boundary\r\nContent-Disposition: form-data; name=file; filename=file.txt\r\n\r\nhello\r\n--boundary--\r\nepilogue";
+
+        let mut reader = MultipartReader::new(ByteReader(data), "boundary");
+        assert_eq!(reader.next_field()?.unwrap().name(), "skipped");
+
+        let field = reader.next_field()?.unwrap();
+        assert_eq!(field.name(), "");
+        assert_eq!(field.headers().len(), 0);
+        assert_eq!(field.into_part()?.value, b"");
+
+        let mut field = reader.next_field()?.unwrap();
+        assert_eq!(field.filename(), Some("file.txt"));
+        let mut value = String::new();
+        field.read_to_string(&mut value)?;
+        assert_eq!(value, "hello");
+
+        assert!(reader.next_field()?.is_none());
+        assert!(reader.next_field()?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reader_incomplete() -> Result<()> {
+        let data = b"--boundary\r\nContent-Disposition: form-data; name=field\r\n\r\nvalue";
+        let mut reader = MultipartReader::new(ByteReader(data), "boundary");
+        let mut field = reader.next_field()?.unwrap();
+        assert!(field.read_to_end(&mut vec![]).is_err());
+
+        let mut reader = MultipartReader::new(ByteReader(b"no boundary"), "boundary");
+        assert!(reader.next_field().is_err());
+        Ok(())
+    }
+
+    fn parse_with(body: &[u8], config: MultipartConfig) -> Result<Multipart> {
+        MultipartReader::new(body, "boundary")
+            .config(config)
+            .into_multipart()
+    }
+
+    #[track_caller]
+    fn multipart_error(result: Result<Multipart>) -> MultipartError {
+        result
+            .err()
+            .and_then(|e| e.downcast::<MultipartError>().ok())
+            .expect("expected a multipart error")
+    }
+
+    #[test]
+    fn test_config() -> Result<()> {
+        let data = b"--boundary\r\nContent-Disposition: form-data; name=text\r\n\r\nvalue\r\n--boundary\r\nContent-Disposition: form-data; name=avatar; filename=a.png\r\nContent-Type: image/png\r\n\r\nimage data\r\n--boundary--";
+
+        let config = MultipartConfig::new()
+            .max_parts(2)
+            .max_field_bytes(5)
+            .max_file_bytes(10)
+            .max_total_bytes(data.len() as u64)
+            .allowed_mime_types("avatar", ["image/*".parse()?])
+            .allowed_mime_types("text", [mime::TEXT_PLAIN])
+            .required("text");
+        assert_eq!(parse_with(data, config.clone())?.len(), 2);
+
+        let e = multipart_error(parse_with(data, config.clone().max_parts(1)));
+        assert_eq!(e, MultipartError::TooManyParts { limit: 1 });
+        assert_eq!(e.status_code(), 413);
+
+        let e = multipart_error(parse_with(data, config.clone().max_field_bytes(4)));
+        assert_eq!(
+            e,
+            MultipartError::FieldTooLarge {
+                name: "text".into(),
+                limit: 4
+            }
+        );
+
+        let e = multipart_error(parse_with(data, config.clone().max_file_bytes(9)));
+        assert_eq!(
+            e,
+            MultipartError::FileTooLarge {
+                name: "avatar".into(),
+                limit: 9
+            }
+        );
+
+        let e = multipart_error(parse_with(data, config.clone().max_total_bytes(64)));
+        assert_eq!(e, MultipartError::BodyTooLarge { limit: 64 });
+
+        let e = multipart_error(parse_with(
+            data,
+            MultipartConfig::new().allowed_mime_types("text", [mime::APPLICATION_JSON]),
+        ));
+        assert_eq!(
+            e,
+            MultipartError::UnsupportedMediaType {
+                name: "text".into(),
+                mime: "text/plain".into()
+            }
+        );
+        assert_eq!(e.status_code(), 415);
+
+        let e = multipart_error(parse_with(data, config.required("missing")));
+        assert_eq!(
+            e,
+            MultipartError::MissingField {
+                name: "missing".into()
+            }
+        );
+        assert_eq!(e.status_code(), 400);
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_reader() -> Result<()> {
+        let data = b"--boundary\r\nContent-Disposition: form-data; name=file; filename=a.txt\r\n\r\nhello world\r\n--boundary--";
+        let mut reader = MultipartReader::new(ByteReader(data), "boundary")
+            .config(MultipartConfig::new().max_file_bytes(5));
+        let mut field = reader.next_field()?.unwrap();
+        let e = field.read_to_end(&mut vec![]).unwrap_err();
+        assert_eq!(
+            MultipartError::from_io(&e),
+            Some(&MultipartError::FileTooLarge {
+                name: "file".into(),
+                limit: 5
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_request_too_large() {
+        let request = |content_length: Option<u64>| {
+            let mut req = crate::Request::new(crate::Method::Post, Default::default());
+            req.headers.insert(
+                CONTENT_TYPE,
+                "multipart/form-data; boundary=b".parse().unwrap(),
+            );
+            if let Some(len) = content_length {
+                req.headers
+                    .insert(crate::header::CONTENT_LENGTH, len.into());
+            }
+            req.body = crate::body::Body::from(
+                b"--b\r\nContent-Disposition: form-data; name=a\r\n\r\n0123456789\r\n--b--"
+                    .to_vec(),
+            );
+            req
+        };
+        let config = MultipartConfig::new().max_total_bytes(16);
+        for content_length in [Some(1 << 20), None] {
+            let e = request(content_length)
+                .multipart_with(&config)
+                .map(|_| ())
+                .unwrap_err();
+            assert_eq!(
+                e.downcast_ref::<MultipartError>(),
+                Some(&MultipartError::BodyTooLarge { limit: 16 })
+            );
+        }
+        assert!(request(None)
+            .multipart_with(&MultipartConfig::new())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_preamble() -> Result<()> {
+        let body = b"--b\r\nContent-Disposition: form-data; name=a\r\n\r\nvalue\r\n--b--";
+        // the preamble spans several reads
+        let preamble = vec![b'-'; 60_000];
+        let parts = parse(&[&preamble[..], b"\r\n", body].concat(), "b")?;
+        assert_eq!(parts.get("a").unwrap().value, b"value");
+
+        let preamble = io::repeat(b'x').take(1 << 30);
+        let e = MultipartReader::new(preamble, "b")
+            .into_multipart()
+            .map(|_| ())
+            .unwrap_err();
+        assert!(e.to_string().contains("preamble exceeds"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_body_limit() {
+        let body = b"--b\r\nContent-Disposition: form-data; name=a\r\n\r\n0123456789\r\n--b--";
+        let e = MultipartReader::new(&body[..], "b")
+            .body_limit(16)
+            .into_multipart()
+            .map(|_| ())
+            .unwrap_err();
+        assert_eq!(
+            e.downcast_ref::<MultipartError>(),
+            Some(&MultipartError::BodyTooLarge { limit: 16 })
+        );
+        // the config takes precedence
+        assert!(MultipartReader::new(&body[..], "b")
+            .body_limit(16)
+            .config(MultipartConfig::new().max_total_bytes(1024))
+            .into_multipart()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_headers_too_large() {
+        let data = format!(
+            "--boundary\r\nContent-Disposition: form-data; name=field\r\nX-Padding: {}\r\n\r\nvalue\r\n--boundary--",
+            "a".repeat(constants::MAX_HEADERS_SIZE)
+        );
+        assert!(parse(data.as_bytes(), "boundary").is_err());
+    }
 }
@@ -0,0 +1,311 @@
+use crate::{
+    header::{HeaderMap, HeaderName, ACCEPT, ACCEPT_ENCODING, ACCEPT_LANGUAGE},
+    Request, Response,
+};
+use mime::Mime;
+use std::fmt;
+
+/// The error returned when none of the available representations is acceptable to the client.
+///
+/// It can be converted into a `406 Not Acceptable` response:
+///
+/// ```
+/// # use waki::{ErrorCode, Request, Response};
+/// fn handler(req: Request) -> Result<Response, ErrorCode> {
+///     let mime = match req.negotiate(&[mime::APPLICATION_JSON, mime::TEXT_HTML]) {
+///         Ok(mime) => mime,
+///         Err(e) => return Ok(e.into()),
+///     };
+///     Response::builder()
+///         .header("Content-Type", mime.as_ref())
+///         .build()
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotAcceptable;
+
+impl NotAcceptable {
+    /// Get the HTTP status code that the error should be answered with.
+    #[inline]
+    pub fn status_code(&self) -> u16 {
+        406
+    }
+}
+
+impl fmt::Display for NotAcceptable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no acceptable representation available")
+    }
+}
+
+impl std::error::Error for NotAcceptable {}
+
+impl From<NotAcceptable> for Response {
+    fn from(e: NotAcceptable) -> Self {
+        Response::builder()
+            .status_code(e.status_code())
+            .build()
+            .unwrap()
+    }
+}
+
+impl Request {
+    /// Choose the media type to respond with, according to the `Accept` header.
+    ///
+    /// Quality values and wildcards such as `text/*` are supported, the most specific range
+    /// matching a media type decides its quality. Ties are resolved by the order of `available`,
+    /// which is also used when the header is missing.
+    pub fn negotiate(&self, available: &[Mime]) -> Result<Mime, NotAcceptable> {
+        negotiate_media_type(&self.headers, available).cloned()
+    }
+
+    /// Choose the language to respond with, according to the `Accept-Language` header.
+    ///
+    /// Language ranges match by prefix, e.g. `en` matches `en-US`.
+    pub fn negotiate_language<'a>(&self, available: &[&'a str]) -> Result<&'a str, NotAcceptable> {
+        negotiate_language(&self.headers, available).copied()
+    }
+
+    /// Choose the content coding to respond with, according to the `Accept-Encoding` header.
+    ///
+    /// `identity` is acceptable unless it is excluded explicitly, e.g. by `identity;q=0`.
+    pub fn negotiate_encoding<'a>(&self, available: &[&'a str]) -> Result<&'a str, NotAcceptable> {
+        negotiate_encoding(&self.headers, available).copied()
+    }
+}
+
+fn negotiate_media_type<'a>(
+    headers: &HeaderMap,
+    available: &'a [Mime],
+) -> Result<&'a Mime, NotAcceptable> {
+    let Some(value) = header_value(headers, ACCEPT).filter(|v| !v.trim().is_empty()) else {
+        return available.first().ok_or(NotAcceptable);
+    };
+    let ranges = parse_list(&value)
+        .filter_map(|(item, q)| {
+            // some clients send a bare `*`
+            let range = match item {
+                "*" => mime::STAR_STAR,
+                _ => item.parse::<Mime>().ok()?,
+            };
+            Some((range, q))
+        })
+        .collect::<Vec<_>>();
+    select(available, &ranges, |mime, range| {
+        if range.type_() == mime::STAR {
+            Some(0)
+        } else if range.type_() != mime.type_() {
+            None
+        } else if range.subtype() == mime::STAR {
+            Some(1)
+        } else if range.subtype() != mime.subtype() {
+            None
+        } else {
+            let mut params = range.params().filter(|(name, _)| name.as_str() != "q");
+            let mut specificity = 2;
+            params.try_for_each(|(name, value)| {
+                specificity = 3;
+                (mime.get_param(name) == Some(value)).then_some(())
+            })?;
+            Some(specificity)
+        }
+    })
+    .ok_or(NotAcceptable)
+}
+
+fn negotiate_language<'a, 'b>(
+    headers: &HeaderMap,
+    available: &'b [&'a str],
+) -> Result<&'b &'a str, NotAcceptable> {
+    let Some(value) = header_value(headers, ACCEPT_LANGUAGE).filter(|v| !v.trim().is_empty())
+    else {
+        return available.first().ok_or(NotAcceptable);
+    };
+    let ranges = parse_list(&value).collect::<Vec<_>>();
+    select(available, &ranges, |tag, range| {
+        if *range == "*" {
+            Some(0)
+        } else if tag.eq_ignore_ascii_case(range)
+            || (tag.len() > range.len()
+                && tag.is_char_boundary(range.len())
+                && tag[..range.len()].eq_ignore_ascii_case(range)
+                && tag.as_bytes()[range.len()] == b'-')
+        {
+            Some(range.len())
+        } else {
+            None
+        }
+    })
+    .ok_or(NotAcceptable)
+}
+
+fn negotiate_encoding<'a, 'b>(
+    headers: &HeaderMap,
+    available: &'b [&'a str],
+) -> Result<&'b &'a str, NotAcceptable> {
+    let Some(value) = header_value(headers, ACCEPT_ENCODING) else {
+        return available.first().ok_or(NotAcceptable);
+    };
+    let mut ranges = parse_list(&value).collect::<Vec<_>>();
+    // identity is always acceptable unless excluded, which `*;q=0` also does, but only preferred
+    // when nothing else is
+    if !ranges
+        .iter()
+        .any(|(range, _)| *range == "*" || range.eq_ignore_ascii_case("identity"))
+    {
+        ranges.push(("identity", 0.001));
+    }
+    select(available, &ranges, |coding, range| {
+        if *range == "*" {
+            Some(0)
+        } else {
+            coding.eq_ignore_ascii_case(range).then_some(1)
+        }
+    })
+    .ok_or(NotAcceptable)
+}
+
+/// Join all values of the header, as they are equivalent to a single comma-separated value.
+fn header_value(headers: &HeaderMap, name: HeaderName) -> Option<String> {
+    let values = headers
+        .get_all(name)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .collect::<Vec<_>>();
+    (!values.is_empty()).then(|| values.join(","))
+}
+
+/// Parse a comma-separated list of items with optional quality values, e.g.
+/// `text/html;level=1, text/*;q=0.5`. Items with an invalid quality value are skipped.
+///
+/// The items are returned with their parameters, except for media types only the value
+/// before the first `;` matters.
+fn parse_list(value: &str) -> impl Iterator<Item = (&str, f32)> {
+    value.split(',').filter_map(|item| {
+        let item = item.trim();
+        if item.is_empty() {
+            return None;
+        }
+        let mut params = item.split(';');
+        let value = params.next()?.trim();
+        let mut q = 1.0;
+        for param in params {
+            if let Some((name, v)) = param.split_once('=') {
+                if name.trim().eq_ignore_ascii_case("q") {
+                    q = v
+                        .trim()
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|q| (0.0..=1.0).contains(q))?;
+                }
+            }
+        }
+        // media types need their parameters to be matched
+        Some((if value.contains('/') { item } else { value }, q))
+    })
+}
+
+/// Select the available item with the highest quality, each item takes the quality of the most
+/// specific range matching it.
+fn select<'a, T, R>(
+    available: &'a [T],
+    ranges: &[(R, f32)],
+    matches: impl Fn(&T, &R) -> Option<usize>,
+) -> Option<&'a T> {
+    let mut best: Option<(&T, f32)> = None;
+    for item in available {
+        let q = ranges
+            .iter()
+            .filter_map(|(range, q)| Some((matches(item, range)?, *q)))
+            .max_by_key(|(specificity, _)| *specificity)
+            .map(|(_, q)| q)
+            .unwrap_or_default();
+        if q > 0.0 && best.map_or(true, |(_, best)| q > best) {
+            best = Some((item, q));
+        }
+    }
+    best.map(|(item, _)| item)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::HeaderValue;
+
+    fn headers(name: HeaderName, values: &[&'static str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(name.clone(), HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    #[test]
+    fn test_media_type() {
+        let available = [mime::APPLICATION_JSON, mime::TEXT_HTML];
+        let negotiate = |values: &[&'static str]| {
+            negotiate_media_type(&headers(ACCEPT, values), &available).cloned()
+        };
+
+        assert_eq!(negotiate(&[]), Ok(mime::APPLICATION_JSON));
+        assert_eq!(negotiate(&["text/html"]), Ok(mime::TEXT_HTML));
+        assert_eq!(negotiate(&["text/*"]), Ok(mime::TEXT_HTML));
+        assert_eq!(negotiate(&["*/*"]), Ok(mime::APPLICATION_JSON));
+        assert_eq!(negotiate(&["*; q=.2"]), Ok(mime::APPLICATION_JSON));
+        assert_eq!(
+            negotiate(&["text/html;q=0.9, application/json;q=0.8"]),
+            Ok(mime::TEXT_HTML)
+        );
+        assert_eq!(
+            negotiate(&["text/html;q=0.5", "application/*;q=0.6"]),
+            Ok(mime::APPLICATION_JSON)
+        );
+        // the most specific range wins
+        assert_eq!(
+            negotiate(&["*/*;q=0.1, application/json;q=0"]),
+            Ok(mime::TEXT_HTML)
+        );
+        assert_eq!(negotiate(&["image/png"]), Err(NotAcceptable));
+        assert_eq!(negotiate(&["*/*;q=0"]), Err(NotAcceptable));
+        assert_eq!(negotiate(&["text/html;q=2"]), Err(NotAcceptable));
+
+        let available = [mime::TEXT_PLAIN, mime::TEXT_PLAIN_UTF_8];
+        assert_eq!(
+            negotiate_media_type(&headers(ACCEPT, &["text/plain;charset=utf-8"]), &available),
+            Ok(&mime::TEXT_PLAIN_UTF_8)
+        );
+    }
+
+    #[test]
+    fn test_language() {
+        let available = ["en-US", "fr", "zh-Hans-CN"];
+        let negotiate = |values: &[&'static str]| {
+            negotiate_language(&headers(ACCEPT_LANGUAGE, values), &available).copied()
+        };
+
+        assert_eq!(negotiate(&[]), Ok("en-US"));
+        assert_eq!(negotiate(&["fr-CH, fr;q=0.9, en;q=0.8"]), Ok("fr"));
+        assert_eq!(negotiate(&["zh-hans"]), Ok("zh-Hans-CN"));
+        assert_eq!(negotiate(&["en-GB, *;q=0.5"]), Ok("en-US"));
+        assert_eq!(negotiate(&["*, fr;q=0"]), Ok("en-US"));
+        assert_eq!(negotiate(&["e"]), Err(NotAcceptable));
+        assert_eq!(negotiate(&["de"]), Err(NotAcceptable));
+    }
+
+    #[test]
+    fn test_encoding() {
+        let available = ["br", "gzip", "identity"];
+        let negotiate = |values: &[&'static str]| {
+            negotiate_encoding(&headers(ACCEPT_ENCODING, values), &available).copied()
+        };
+
+        assert_eq!(negotiate(&[]), Ok("br"));
+        assert_eq!(negotiate(&[""]), Ok("identity"));
+        assert_eq!(negotiate(&["gzip, deflate"]), Ok("gzip"));
+        assert_eq!(negotiate(&["gzip;q=0.5, br;q=0.4"]), Ok("gzip"));
+        assert_eq!(negotiate(&["GZIP;q=0.5", "*;q=0.6"]), Ok("br"));
+        assert_eq!(negotiate(&["deflate"]), Ok("identity"));
+        assert_eq!(negotiate(&["deflate, identity;q=0"]), Err(NotAcceptable));
+        assert_eq!(negotiate(&["*;q=0"]), Err(NotAcceptable));
+    }
+}
@@ -0,0 +1,351 @@
+use crate::{
+    body::Body,
+    header::{HeaderMap, ACCEPT_RANGES, CONTENT_LENGTH},
+    headers::{ContentRange, ETag, Header, IfRange, LastModified, Range},
+    Method, Request, ResponseBuilder,
+};
+#[cfg(feature = "multipart")]
+use crate::{
+    header::CONTENT_TYPE,
+    multipart::{Form, Part},
+};
+use std::cell::RefCell;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::rc::Rc;
+
+impl ResponseBuilder {
+    /// Set the body from a source of `len` bytes, answering the `Range` header of the request.
+    ///
+    /// - a single satisfiable range results in `206 Partial Content` with the `Content-Range`,
+    /// - multiple ranges result in `206 Partial Content` with a `multipart/byteranges` body, each
+    ///   part having the Content-Type that was set on the builder, overlapping and adjacent
+    ///   ranges are coalesced,
+    /// - no satisfiable range results in `416 Range Not Satisfiable`,
+    /// - otherwise, or if more than 16 ranges are requested, the whole source is sent.
+    ///
+    /// `Accept-Ranges: bytes` is always set. The `If-Range` header is evaluated against the
+    /// `ETag` and `Last-Modified` headers set on the builder, so they must be set before.
+    ///
+    /// # Optional
+    ///
+    /// Multiple ranges require the `multipart` feature, without it the whole source is sent.
+    ///
+    /// ```
+    /// # use std::io::Cursor;
+    /// # use waki::{ErrorCode, Request, Response};
+    /// fn handler(req: Request) -> Result<Response, ErrorCode> {
+    ///     let data = vec![0; 1 << 20];
+    ///     let len = data.len() as u64;
+    ///     Response::builder()
+    ///         .header("Content-Type", "application/octet-stream")
+    ///         .ranged_body(&req, Cursor::new(data), len)
+    ///         .build()
+    /// }
+    /// ```
+    pub fn ranged_body<R: Read + Seek + 'static>(
+        mut self,
+        req: &Request,
+        source: R,
+        len: u64,
+    ) -> Self {
+        let Ok(ref mut inner) = self.inner else {
+            return self;
+        };
+        inner
+            .headers
+            .insert(ACCEPT_RANGES, "bytes".parse().unwrap());
+        let ranges = match req.typed_header::<Range>() {
+            Some(Range(ranges))
+                if matches!(req.method(), Method::Get | Method::Head)
+                    && ranges.len() <= MAX_RANGES
+                    && if_range(req, &inner.headers) =>
+            {
+                Some(coalesce(
+                    ranges
+                        .iter()
+                        .filter_map(|range| range.resolve(len))
+                        .collect(),
+                ))
+            }
+            _ => None,
+        };
+        let source = Rc::new(RefCell::new(source));
+        #[cfg(feature = "multipart")]
+        let mut multipart = None;
+
+        let status_code = match ranges.as_deref() {
+            Some([]) => {
+                insert(&mut inner.headers, ContentRange::unsatisfied(len));
+                inner.headers.insert(CONTENT_LENGTH, 0.into());
+                inner.body = Body::from(vec![]);
+                416
+            }
+            Some(&[(first, last)]) => {
+                insert(
+                    &mut inner.headers,
+                    ContentRange::bytes(first, last, Some(len)),
+                );
+                inner
+                    .headers
+                    .insert(CONTENT_LENGTH, (last - first + 1).into());
+                inner.body = Body::reader(Slice::new(source, first, last + 1));
+                206
+            }
+            #[cfg(feature = "multipart")]
+            Some(ranges) => {
+                let mut form = Form::with_subtype("byteranges");
+                for &(first, last) in ranges {
+                    let mut part = Part::new("", vec![]);
+                    if let Some(content_type) = inner.headers.get(CONTENT_TYPE) {
+                        part.headers.insert(CONTENT_TYPE, content_type.clone());
+                    }
+                    insert(
+                        &mut part.headers,
+                        ContentRange::bytes(first, last, Some(len)),
+                    );
+                    form = form.part_reader(
+                        part,
+                        Slice::new(source.clone(), first, last + 1),
+                        Some(last - first + 1),
+                    );
+                }
+                multipart = Some(form);
+                206
+            }
+            _ => {
+                inner.headers.insert(CONTENT_LENGTH, len.into());
+                inner.body = Body::reader(Slice::new(source, 0, len));
+                200
+            }
+        };
+        #[cfg(feature = "multipart")]
+        if let Some(form) = multipart {
+            self = self.multipart(form);
+        }
+        self.status_code(status_code)
+    }
+}
+
+// The maximum number of ranges answered, to bound the work done for a single request.
+const MAX_RANGES: usize = 16;
+
+fn insert<H: Header>(headers: &mut HeaderMap, header: H) {
+    // the ranges are resolved, so the header is always valid
+    headers.insert(H::name(), header.encode().unwrap());
+}
+
+/// Evaluate the `If-Range` header, the Range header is only applied if it passes.
+fn if_range(req: &Request, headers: &HeaderMap) -> bool {
+    let name = IfRange::name();
+    if !req.headers.contains_key(&name) {
+        return true;
+    }
+    let current = |name| headers.get_all(name).iter();
+    match req.typed_header::<IfRange>() {
+        Some(IfRange::ETag(tag)) => {
+            matches!(ETag::decode(current(ETag::name())), Ok(ETag(etag)) if etag.strong_eq(&tag))
+        }
+        Some(IfRange::Date(date)) => matches!(
+            LastModified::decode(current(LastModified::name())),
+            Ok(LastModified(last_modified)) if last_modified == date
+        ),
+        None => false,
+    }
+}
+
+/// Sort the ranges and merge the ones that overlap or are adjacent.
+fn coalesce(mut ranges: Vec<(u64, u64)>) -> Vec<(u64, u64)> {
+    if ranges.len() < 2 {
+        return ranges;
+    }
+    ranges.sort_unstable();
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for (first, last) in ranges {
+        match merged.last_mut() {
+            Some(prev) if first <= prev.1.saturating_add(1) => prev.1 = prev.1.max(last),
+            _ => merged.push((first, last)),
+        }
+    }
+    merged
+}
+
+/// Reads the bytes from `pos` to `end` of a source shared with other slices.
+struct Slice<R> {
+    source: Rc<RefCell<R>>,
+    pos: u64,
+    end: u64,
+}
+
+impl<R> Slice<R> {
+    fn new(source: Rc<RefCell<R>>, start: u64, end: u64) -> Self {
+        Self {
+            source,
+            pos: start,
+            end,
+        }
+    }
+}
+
+impl<R: Read + Seek> Read for Slice<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.end - self.pos;
+        if remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+        let max = buf
+            .len()
+            .min(usize::try_from(remaining).unwrap_or(usize::MAX));
+        let mut source = self.source.borrow_mut();
+        source.seek(SeekFrom::Start(self.pos))?;
+        let n = source.read(&mut buf[..max])?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        header::{HeaderValue, CONTENT_RANGE, CONTENT_TYPE},
+        Response,
+    };
+    use std::io::Cursor;
+
+    const DATA: &[u8] = b"0123456789";
+    const DATE: &str = "Sun, 06 Nov 1994 08:49:37 GMT";
+
+    fn serve(method: Method, headers: &[(&'static str, &'static str)]) -> Response {
+        let mut req = Request::new(method, Default::default());
+        for (k, v) in headers {
+            req.headers.insert(*k, HeaderValue::from_static(v));
+        }
+        Response::builder()
+            .header(CONTENT_TYPE, "text/plain")
+            .header("etag", "\"v1\"")
+            .header("last-modified", DATE)
+            .ranged_body(&req, Cursor::new(DATA), DATA.len() as u64)
+            .build()
+            .unwrap()
+    }
+
+    #[track_caller]
+    fn assert_response(resp: Response, status_code: u16, content_range: Option<&str>, body: &[u8]) {
+        assert_eq!(resp.status_code(), status_code);
+        assert_eq!(resp.header(ACCEPT_RANGES).unwrap(), "bytes");
+        assert_eq!(
+            resp.header(CONTENT_RANGE).map(|v| v.to_str().unwrap()),
+            content_range
+        );
+        assert_eq!(
+            resp.header(CONTENT_LENGTH).unwrap(),
+            &body.len().to_string()
+        );
+        assert_eq!(resp.body().unwrap(), body);
+    }
+
+    #[test]
+    fn test_single_range() {
+        let range = |value| serve(Method::Get, &[("range", value)]);
+        assert_response(range("bytes=2-4"), 206, Some("bytes 2-4/10"), b"234");
+        assert_response(range("bytes=7-"), 206, Some("bytes 7-9/10"), b"789");
+        assert_response(range("bytes=-3"), 206, Some("bytes 7-9/10"), b"789");
+        assert_response(range("bytes=5-100"), 206, Some("bytes 5-9/10"), b"56789");
+        // unsatisfiable ranges are ignored if another one is satisfiable
+        assert_response(range("bytes=20-30, 0-0"), 206, Some("bytes 0-0/10"), b"0");
+        // coalesced into a single range
+        assert_response(
+            range("bytes=0-2, 3-5, 1-4"),
+            206,
+            Some("bytes 0-5/10"),
+            b"012345",
+        );
+    }
+
+    #[test]
+    fn test_not_satisfiable() {
+        let range = |value| serve(Method::Get, &[("range", value)]);
+        assert_response(range("bytes=10-"), 416, Some("bytes */10"), b"");
+        assert_response(range("bytes=-0, 20-30"), 416, Some("bytes */10"), b"");
+    }
+
+    #[test]
+    fn test_full() {
+        assert_response(serve(Method::Get, &[]), 200, None, DATA);
+        // invalid ranges are ignored
+        assert_response(
+            serve(Method::Get, &[("range", "bytes=5-1")]),
+            200,
+            None,
+            DATA,
+        );
+        assert_response(
+            serve(Method::Get, &[("range", "items=0-1")]),
+            200,
+            None,
+            DATA,
+        );
+        // only for GET and HEAD
+        assert_response(
+            serve(Method::Post, &[("range", "bytes=0-1")]),
+            200,
+            None,
+            DATA,
+        );
+    }
+
+    #[test]
+    fn test_too_many_ranges() {
+        let range = |value| serve(Method::Get, &[("range", value)]);
+        // up to 16 ranges are answered, here coalesced into a single one
+        assert_response(
+            range("bytes=0-0,1-1,2-2,3-3,4-4,5-5,6-6,7-7,8-8,9-9,0-1,2-3,4-5,6-7,8-9,0-2"),
+            206,
+            Some("bytes 0-9/10"),
+            DATA,
+        );
+        assert_response(
+            range("bytes=0-0,1-1,2-2,3-3,4-4,5-5,6-6,7-7,8-8,9-9,0-1,2-3,4-5,6-7,8-9,0-2,3-5"),
+            200,
+            None,
+            DATA,
+        );
+    }
+
+    #[test]
+    fn test_if_range() {
+        let range = |value| serve(Method::Get, &[("range", "bytes=0-1"), ("if-range", value)]);
+        assert_response(range("\"v1\""), 206, Some("bytes 0-1/10"), b"01");
+        assert_response(range(DATE), 206, Some("bytes 0-1/10"), b"01");
+        assert_response(range("\"v2\""), 200, None, DATA);
+        assert_response(range("W/\"v1\""), 200, None, DATA);
+        assert_response(range("Mon, 07 Nov 1994 08:49:37 GMT"), 200, None, DATA);
+        assert_response(range("invalid"), 200, None, DATA);
+    }
+
+    #[cfg(feature = "multipart")]
+    #[test]
+    fn test_multiple_ranges() -> anyhow::Result<()> {
+        let resp = serve(Method::Get, &[("range", "bytes=7-8, 0-1")]);
+        assert_eq!(resp.status_code(), 206);
+        assert!(resp.header(CONTENT_RANGE).is_none());
+        let content_type = resp.header(CONTENT_TYPE).unwrap().to_str()?;
+        assert!(content_type.starts_with("multipart/byteranges; boundary="));
+
+        let parts = resp.multipart()?;
+        let parts = parts.iter().collect::<Vec<_>>();
+        assert_eq!(parts.len(), 2);
+        for (part, (range, value)) in parts
+            .iter()
+            .zip([("bytes 0-1/10", "01"), ("bytes 7-8/10", "78")])
+        {
+            assert_eq!(part.headers.get(CONTENT_RANGE).unwrap(), range);
+            assert_eq!(part.mime, Some(mime::TEXT_PLAIN));
+            assert_eq!(part.value, value.as_bytes());
+        }
+        Ok(())
+    }
+}
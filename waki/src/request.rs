@@ -4,8 +4,10 @@ use crate::{
         types::{IncomingRequest, OutgoingBody, OutgoingRequest, RequestOptions},
     },
     body::{write_to_outgoing_body, Body},
+    cache::Cache,
+    common::{de, ser},
     header::HeaderMap,
-    ErrorCode, Method, Response,
+    ErrorCode, Method, Proxy, Response,
 };
 
 use anyhow::{anyhow, Error, Result};
@@ -13,13 +15,17 @@ use http::{
     uri::{Authority, Parts, PathAndQuery},
     Uri,
 };
+use serde::{de::DeserializeOwned, Serialize};
 use std::borrow::Borrow;
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 
 pub struct RequestBuilder {
     // all errors generated while building the request will be deferred and returned when `send` the request.
     pub(crate) inner: Result<Request>,
+    pub(crate) cache: Option<Arc<Cache>>,
+    pub(crate) proxies: Arc<[Proxy]>,
 }
 
 impl RequestBuilder {
@@ -30,6 +36,8 @@ impl RequestBuilder {
                 |e| Err(Error::new(e)),
                 |uri| Ok(Request::new(method, uri.into_parts())),
             ),
+            cache: None,
+            proxies: Arc::new([]),
         }
     }
 
@@ -74,6 +82,46 @@ impl RequestBuilder {
         self
     }
 
+    /// Serialize the value into the query string of the Request URI.
+    ///
+    /// Sequences are serialized as repeated keys, nested structs and maps as `a[b]=c`,
+    /// and `None` values are skipped.
+    ///
+    /// ```
+    /// # use anyhow::Result;
+    /// # use serde::Serialize;
+    /// # use waki::Client;
+    /// #[derive(Serialize)]
+    /// struct Search {
+    ///     q: String,
+    ///     page: Option<u32>,
+    ///     tag: Vec<String>,
+    /// }
+    ///
+    /// # fn run() -> Result<()> {
+    /// // https://httpbin.org/get?q=waki&tag=wasi&tag=http
+    /// let resp = Client::new().get("https://httpbin.org/get")
+    ///     .query_serde(&Search {
+    ///         q: "waki".into(),
+    ///         page: None,
+    ///         tag: vec!["wasi".into(), "http".into()],
+    ///     })
+    ///     .send()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn query_serde<T: Serialize + ?Sized>(mut self, query: &T) -> Self {
+        match ser::to_pairs(query) {
+            Ok(pairs) => self.query(pairs),
+            Err(e) => {
+                if self.inner.is_ok() {
+                    self.inner = Err(e.into());
+                }
+                self
+            }
+        }
+    }
+
     /// Set the timeout for the initial connect to the HTTP Server.
     ///
     /// ```
@@ -104,19 +152,28 @@ impl RequestBuilder {
     /// Send the Request, returning a [`Response`].
     #[inline]
     pub fn send(self) -> Result<Response> {
-        match self.inner {
-            Ok(req) => req.send(),
-            Err(e) => Err(e),
+        let proxies = self.proxies;
+        let fetch = |mut req: Request| {
+            Proxy::apply(&proxies, &mut req)?;
+            req.send()
+        };
+        match (self.inner, self.cache) {
+            (Ok(req), Some(cache)) => cache.send(req, fetch),
+            (Ok(req), None) => fetch(req),
+            (Err(e), _) => Err(e),
         }
     }
 }
 
 pub struct Request {
     method: Method,
-    uri: Parts,
+    pub(crate) uri: Parts,
     pub(crate) headers: HeaderMap,
     pub(crate) body: Body,
+    pub(crate) trailers: Option<HeaderMap>,
     connect_timeout: Option<u64>,
+    // the absolute-form target sent to a proxy
+    pub(crate) request_target: Option<String>,
 }
 
 impl TryFrom<IncomingRequest> for Request {
@@ -160,7 +217,9 @@ impl TryFrom<IncomingRequest> for Request {
             uri: parts,
             headers,
             body: Body::Stream(incoming_body.into()),
+            trailers: None,
             connect_timeout: None,
+            request_target: None,
         })
     }
 }
@@ -172,8 +231,10 @@ impl Request {
             method,
             uri,
             headers: HeaderMap::new(),
-            body: Body::Bytes(vec![]),
+            body: Body::from(vec![]),
+            trailers: None,
             connect_timeout: None,
+            request_target: None,
         }
     }
 
@@ -199,13 +260,48 @@ impl Request {
 
     /// Get the query string of the request.
     pub fn query(&self) -> HashMap<String, String> {
+        form_urlencoded::parse(self.query_str().as_bytes())
+            .into_owned()
+            .collect()
+    }
+
+    /// Get the query pairs of the request, in order and including repeated keys.
+    pub fn query_pairs(&self) -> Vec<(String, String)> {
+        form_urlencoded::parse(self.query_str().as_bytes())
+            .into_owned()
+            .collect()
+    }
+
+    /// Deserialize the query string into `T`.
+    ///
+    /// Repeated keys (`tag=a&tag=b`) can be collected into a `Vec`, and nested keys
+    /// (`filter[name]=a`) into nested structs or maps. Empty values are treated as `None`
+    /// for optional fields.
+    ///
+    /// ```
+    /// # use anyhow::Result;
+    /// # use serde::Deserialize;
+    /// # use waki::Request;
+    /// #[derive(Deserialize)]
+    /// struct Search {
+    ///     q: String,
+    ///     page: Option<u32>,
+    ///     tag: Vec<String>,
+    /// }
+    ///
+    /// # fn run(req: Request) -> Result<()> {
+    /// let search: Search = req.query_as()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn query_as<T: DeserializeOwned>(&self) -> Result<T> {
+        Ok(de::from_urlencoded(self.query_str().as_bytes())?)
+    }
+
+    fn query_str(&self) -> &str {
         match &self.uri.path_and_query {
-            Some(path_and_query) => {
-                let query_pairs =
-                    form_urlencoded::parse(path_and_query.query().unwrap_or_default().as_bytes());
-                query_pairs.into_owned().collect()
-            }
-            None => HashMap::default(),
+            Some(path_and_query) => path_and_query.query().unwrap_or_default(),
+            None => "",
         }
     }
 
@@ -215,7 +311,7 @@ impl Request {
         &self.uri.authority
     }
 
-    fn send(self) -> Result<Response> {
+    pub(crate) fn send(self) -> Result<Response> {
         let req = OutgoingRequest::new(self.headers.try_into()?);
         req.set_method(&self.method)
             .map_err(|()| anyhow!("failed to set method"))?;
@@ -227,8 +323,12 @@ impl Request {
             req.set_authority(Some(authority.as_str()))
                 .map_err(|()| anyhow!("failed to set authority"))?;
         }
-        if let Some(path_and_query) = self.uri.path_and_query {
-            req.set_path_with_query(Some(path_and_query.as_str()))
+        let path_with_query = match self.request_target {
+            Some(target) => Some(target),
+            None => self.uri.path_and_query.map(|p| p.as_str().to_string()),
+        };
+        if let Some(path_with_query) = path_with_query {
+            req.set_path_with_query(Some(&path_with_query))
                 .map_err(|()| anyhow!("failed to set path_with_query"))?;
         }
 
@@ -242,9 +342,9 @@ impl Request {
             .map_err(|()| anyhow!("failed to set connect_timeout"))?;
         let future_response = outgoing_handler::handle(req, Some(options))?;
 
-        let body = self.body.bytes()?;
-        write_to_outgoing_body(&outgoing_body, body.as_slice())?;
-        OutgoingBody::finish(outgoing_body, None)?;
+        write_to_outgoing_body(&outgoing_body, self.body)?;
+        let trailers = self.trailers.map(TryInto::try_into).transpose()?;
+        OutgoingBody::finish(outgoing_body, trailers)?;
 
         let incoming_response = match future_response.get() {
             Some(result) => result.map_err(|()| anyhow!("response already taken"))?,
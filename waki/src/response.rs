@@ -53,6 +53,7 @@ impl ResponseBuilder {
 pub struct Response {
     pub(crate) headers: HeaderMap,
     pub(crate) body: Body,
+    pub(crate) trailers: Option<HeaderMap>,
     status_code: u16,
 }
 
@@ -77,6 +78,7 @@ impl TryFrom<IncomingResponse> for Response {
             headers,
             status_code,
             body: Body::Stream(incoming_body.into()),
+            trailers: None,
         })
     }
 }
@@ -87,7 +89,8 @@ impl Response {
         Self {
             headers: HeaderMap::new(),
             status_code: 200,
-            body: Body::Bytes(vec![]),
+            body: Body::from(vec![]),
+            trailers: None,
         }
     }
 
@@ -103,6 +106,16 @@ impl Response {
     }
 }
 
+pub fn handle_error(response_out: ResponseOutparam, e: ErrorCode) {
+    match e {
+        ErrorCode::HttpRequestBodySize(_) => handle_response(
+            response_out,
+            Response::builder().status_code(413).build().unwrap(),
+        ),
+        e => ResponseOutparam::set(response_out, Err(e)),
+    }
+}
+
 pub fn handle_response(response_out: ResponseOutparam, response: Response) {
     let outgoing_response = OutgoingResponse::new(response.headers.try_into().unwrap());
     outgoing_response
@@ -111,7 +124,13 @@ pub fn handle_response(response_out: ResponseOutparam, response: Response) {
     let outgoing_body = outgoing_response.body().unwrap();
     ResponseOutparam::set(response_out, Ok(outgoing_response));
 
-    let body = response.body.bytes().unwrap();
-    write_to_outgoing_body(&outgoing_body, body.as_slice()).unwrap();
-    OutgoingBody::finish(outgoing_body, None).unwrap();
+    // a streamed body can fail midway, the response has been sent already so the body is
+    // dropped without being finished, which the host sees as an aborted body
+    if write_to_outgoing_body(&outgoing_body, response.body).is_err() {
+        return;
+    }
+    let Ok(trailers) = response.trailers.map(TryInto::try_into).transpose() else {
+        return;
+    };
+    let _ = OutgoingBody::finish(outgoing_body, trailers);
 }
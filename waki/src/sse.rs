@@ -0,0 +1,425 @@
+//! Server-Sent Events client.
+//!
+//! Read events from a single response:
+//!
+//! ```
+//! # use anyhow::Result;
+//! # use waki::Client;
+//! # fn run() -> Result<()> {
+//! let resp = Client::new()
+//!     .get("https://example.com/events")
+//!     .header("Accept", "text/event-stream")
+//!     .send()?;
+//!
+//! for event in resp.events() {
+//!     let event = event?;
+//!     println!("{}: {}", event.event, event.data);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Or use an [`EventSource`] to reconnect automatically when the stream ends:
+//!
+//! ```
+//! # use anyhow::Result;
+//! # use waki::sse::EventSource;
+//! # fn run() -> Result<()> {
+//! for event in EventSource::get("https://example.com/events") {
+//!     println!("{}", event?.data);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    bindings::wasi::clocks::monotonic_clock::subscribe_duration,
+    header::{ACCEPT, CACHE_CONTROL, CONTENT_TYPE},
+    Client, RequestBuilder, Response,
+};
+
+use anyhow::{anyhow, Result};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+const DEFAULT_RETRY: Duration = Duration::from_secs(3);
+const CHUNK_SIZE: u64 = 8 * 1024;
+
+/// A single event received from an event stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    /// The event type, `message` if the `event` field was not set.
+    pub event: String,
+    /// The event payload, multiple `data` fields are joined with `\n`.
+    pub data: String,
+    /// The last event ID at the time the event was dispatched.
+    pub id: Option<String>,
+}
+
+/// Incremental decoder for the `text/event-stream` format.
+#[derive(Default)]
+struct Decoder {
+    line: Vec<u8>,
+    // the previous chunk ended with a CR, so a leading LF belongs to the same line break
+    pending_cr: bool,
+    bom_checked: bool,
+    event: String,
+    data: String,
+    last_event_id: String,
+    retry: Option<Duration>,
+}
+
+impl Decoder {
+    fn feed(&mut self, mut bytes: &[u8], events: &mut VecDeque<Event>) {
+        if !self.bom_checked && !bytes.is_empty() {
+            self.bom_checked = true;
+            bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+        }
+        for &b in bytes {
+            if self.pending_cr {
+                self.pending_cr = false;
+                if b == b'\n' {
+                    continue;
+                }
+            }
+            match b {
+                b'\r' | b'\n' => {
+                    self.pending_cr = b == b'\r';
+                    let line = std::mem::take(&mut self.line);
+                    if let Some(event) = self.process_line(&String::from_utf8_lossy(&line)) {
+                        events.push_back(event);
+                    }
+                }
+                _ => self.line.push(b),
+            }
+        }
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<Event> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = value.to_string(),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" if !value.contains('\0') => self.last_event_id = value.to_string(),
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                if let Ok(ms) = value.parse() {
+                    self.retry = Some(Duration::from_millis(ms));
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<Event> {
+        let event = std::mem::take(&mut self.event);
+        if self.data.is_empty() {
+            return None;
+        }
+        let mut data = std::mem::take(&mut self.data);
+        data.pop();
+        Some(Event {
+            event: if event.is_empty() {
+                "message".to_string()
+            } else {
+                event
+            },
+            data,
+            id: (!self.last_event_id.is_empty()).then(|| self.last_event_id.clone()),
+        })
+    }
+
+    // Called when the connection is re-established, any incomplete event is discarded.
+    fn reset(&mut self) {
+        self.line.clear();
+        self.pending_cr = false;
+        self.bom_checked = false;
+        self.event.clear();
+        self.data.clear();
+    }
+}
+
+/// An iterator over the events of a single response, created by [`Response::events`].
+pub struct Events {
+    response: Response,
+    decoder: Decoder,
+    pending: VecDeque<Event>,
+}
+
+impl Events {
+    fn new(response: Response) -> Self {
+        Self {
+            response,
+            decoder: Decoder::default(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Get the ID of the last received event.
+    pub fn last_event_id(&self) -> &str {
+        &self.decoder.last_event_id
+    }
+
+    /// Get the reconnection time requested by the server with the `retry` field.
+    pub fn retry(&self) -> Option<Duration> {
+        self.decoder.retry
+    }
+}
+
+impl Iterator for Events {
+    type Item = Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+            match self.response.chunk(CHUNK_SIZE) {
+                Ok(Some(chunk)) => self.decoder.feed(&chunk, &mut self.pending),
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+impl Response {
+    /// Parse the body as a `text/event-stream`, returning an iterator over the events.
+    ///
+    /// The iterator ends when the stream is closed, use [`EventSource`] to reconnect automatically.
+    pub fn events(self) -> Events {
+        Events::new(self)
+    }
+}
+
+/// A reconnecting event stream client.
+///
+/// When the stream ends or the connection fails, it waits for the reconnection time
+/// (3 seconds by default, or the last `retry` value sent by the server) and sends the request
+/// again with the `Last-Event-ID` header.
+///
+/// Reconnecting stops if the server responds with a status other than 200, or with
+/// a `Content-Type` other than `text/event-stream`.
+pub struct EventSource {
+    request: Box<dyn FnMut() -> RequestBuilder>,
+    reconnect: bool,
+    response: Option<Response>,
+    decoder: Decoder,
+    pending: VecDeque<Event>,
+    retrying: bool,
+    closed: bool,
+}
+
+impl EventSource {
+    /// Create an event source from a request factory, which is called for every (re)connection.
+    ///
+    /// ```
+    /// # use waki::{sse::EventSource, Client};
+    /// let source = EventSource::new(|| {
+    ///     Client::new()
+    ///         .get("https://example.com/events")
+    ///         .header("Authorization", "Bearer token")
+    /// });
+    /// ```
+    pub fn new<F>(request: F) -> Self
+    where
+        F: FnMut() -> RequestBuilder + 'static,
+    {
+        Self {
+            request: Box::new(request),
+            reconnect: true,
+            response: None,
+            decoder: Decoder::default(),
+            pending: VecDeque::new(),
+            retrying: false,
+            closed: false,
+        }
+    }
+
+    /// Create an event source that sends GET requests to the URL.
+    pub fn get(url: &str) -> Self {
+        let url = url.to_string();
+        Self::new(move || Client::new().get(&url))
+    }
+
+    /// Whether to reconnect when the stream ends or the connection fails.
+    ///
+    /// Default value: true.
+    pub fn reconnect(mut self, reconnect: bool) -> Self {
+        self.reconnect = reconnect;
+        self
+    }
+
+    /// Get the ID of the last received event.
+    pub fn last_event_id(&self) -> &str {
+        &self.decoder.last_event_id
+    }
+
+    /// Stop the event source, the iterator will not return any more events.
+    pub fn close(&mut self) {
+        self.closed = true;
+        self.response = None;
+        self.pending.clear();
+    }
+
+    fn send(&mut self) -> Result<Response> {
+        let mut builder = (self.request)()
+            .header(ACCEPT, "text/event-stream")
+            .header(CACHE_CONTROL, "no-cache");
+        if !self.decoder.last_event_id.is_empty() {
+            builder = builder.header("Last-Event-ID", self.decoder.last_event_id.as_str());
+        }
+        builder.send()
+    }
+
+    fn disconnect(&mut self) {
+        self.response = None;
+        if self.reconnect {
+            self.retrying = true;
+        } else {
+            self.closed = true;
+        }
+    }
+}
+
+// Returns false if the server asks the client to stop reconnecting.
+fn validate(resp: &Response) -> Result<bool> {
+    // 204 No Content tells the client to stop reconnecting
+    if resp.status_code() == 204 {
+        return Ok(false);
+    }
+    if resp.status_code() != 200 {
+        return Err(anyhow!(
+            "event stream responded with status code {}",
+            resp.status_code()
+        ));
+    }
+    let content_type = resp
+        .header(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let essence = content_type.split(';').next().unwrap_or_default().trim();
+    if !essence.eq_ignore_ascii_case("text/event-stream") {
+        return Err(anyhow!(
+            "event stream responded with unexpected Content-Type: {content_type}"
+        ));
+    }
+    Ok(true)
+}
+
+impl Iterator for EventSource {
+    type Item = Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+            if self.closed {
+                return None;
+            }
+
+            match &self.response {
+                Some(resp) => match resp.chunk(CHUNK_SIZE) {
+                    Ok(Some(chunk)) => self.decoder.feed(&chunk, &mut self.pending),
+                    Ok(None) => self.disconnect(),
+                    Err(e) => {
+                        self.disconnect();
+                        return Some(Err(e));
+                    }
+                },
+                None => {
+                    if self.retrying {
+                        let retry = self.decoder.retry.unwrap_or(DEFAULT_RETRY);
+                        subscribe_duration(retry.as_nanos() as u64).block();
+                    }
+                    let resp = match self.send() {
+                        Ok(resp) => resp,
+                        Err(e) => {
+                            self.disconnect();
+                            return Some(Err(e));
+                        }
+                    };
+                    // invalid responses fail the connection without reconnecting
+                    match validate(&resp) {
+                        Ok(true) => {
+                            self.decoder.reset();
+                            self.response = Some(resp);
+                        }
+                        Ok(false) => self.closed = true,
+                        Err(e) => {
+                            self.closed = true;
+                            return Some(Err(e));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(chunks: &[&[u8]]) -> (Vec<Event>, Decoder) {
+        let mut decoder = Decoder::default();
+        let mut events = VecDeque::new();
+        for chunk in chunks {
+            decoder.feed(chunk, &mut events);
+        }
+        (events.into(), decoder)
+    }
+
+    #[test]
+    fn test_decode() {
+        let (events, decoder) = decode(&[
+            b"\xEF\xBB\xBF: comment\nevent: update\ndata: first\r\ndata:second\r",
+            b"\nid: 42\n\ndata: third\r\rretry: 1500\nid\n\n",
+            b"data: incomplete",
+        ]);
+        assert_eq!(
+            events,
+            vec![
+                Event {
+                    event: "update".into(),
+                    data: "first\nsecond".into(),
+                    id: Some("42".into()),
+                },
+                Event {
+                    event: "message".into(),
+                    data: "third".into(),
+                    id: Some("42".into()),
+                },
+            ]
+        );
+        assert_eq!(decoder.last_event_id, "");
+        assert_eq!(decoder.retry, Some(Duration::from_millis(1500)));
+    }
+
+    #[test]
+    fn test_decode_ignored_fields() {
+        let (events, decoder) = decode(&[b"event: ping\n\nid: a\0b\nretry: 1s\ndata\n\n"]);
+        assert_eq!(
+            events,
+            vec![Event {
+                event: "message".into(),
+                data: "".into(),
+                id: None,
+            }]
+        );
+        assert_eq!(decoder.retry, None);
+    }
+}
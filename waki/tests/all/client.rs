@@ -1,4 +1,4 @@
-use super::run_wasi;
+use super::{run_wasi, run_wasi_with_env};
 
 #[tokio::test(flavor = "multi_thread")]
 async fn get_chunk() {
@@ -48,3 +48,40 @@ async fn post_with_multipart_form_data() {
         .await
         .unwrap();
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn get_through_proxy() {
+    use std::io::{BufRead, BufReader, Write};
+
+    // a proxy recording the request head
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let proxy = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut lines = vec![];
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim_end().is_empty() {
+                break;
+            }
+            lines.push(line.trim_end().to_string());
+        }
+        reader
+            .get_mut()
+            .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok")
+            .unwrap();
+        lines
+    });
+
+    run_wasi_with_env(
+        test_programs_artifacts::CLIENT_GET_THROUGH_PROXY_COMPONENT,
+        &[("HTTP_PROXY", &format!("http://{addr}"))],
+    )
+    .await
+    .unwrap();
+
+    let lines = proxy.join().unwrap();
+    assert_eq!(lines[0], "GET http://example.com/path?a=b HTTP/1.1");
+}
@@ -30,6 +30,53 @@ async fn json() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn ndjson() -> Result<()> {
+    let req = hyper::Request::builder()
+        .uri("http://localhost")
+        .body(body::full(
+            "{\"value\": 1}\n{\"value\": 2}\r\n\n{\"value\": 3}",
+        ))?;
+
+    let resp = run_wasi_http(test_programs_artifacts::SERVER_NDJSON_COMPONENT, req).await??;
+    let body = resp.into_body().to_bytes();
+    let body = std::str::from_utf8(&body)?;
+    assert_eq!(body, "6");
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn json_stream() -> Result<()> {
+    let req = hyper::Request::builder()
+        .uri("http://localhost/ndjson")
+        .body(body::full(r#"[{"value": 1}, {"value": 2}]"#))?;
+
+    let resp = run_wasi_http(test_programs_artifacts::SERVER_JSON_STREAM_COMPONENT, req).await??;
+    assert_eq!(
+        resp.headers().get("Content-Type").unwrap(),
+        "application/x-ndjson"
+    );
+    let body = resp.into_body().to_bytes();
+    let body = std::str::from_utf8(&body)?;
+    assert_eq!(body, "{\"value\":2}\n{\"value\":4}\n");
+
+    let req = hyper::Request::builder()
+        .uri("http://localhost")
+        .body(body::full(r#"[{"value": 1}, {"value": 2}]"#))?;
+
+    let resp = run_wasi_http(test_programs_artifacts::SERVER_JSON_STREAM_COMPONENT, req).await??;
+    assert_eq!(
+        resp.headers().get("Content-Type").unwrap(),
+        "application/json"
+    );
+    let body = resp.into_body().to_bytes();
+    let body = std::str::from_utf8(&body)?;
+    assert_eq!(body, r#"[{"value":2},{"value":4}]"#);
+
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn large_body() -> Result<()> {
     let req = hyper::Request::builder()
@@ -43,6 +90,33 @@ async fn large_body() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn body_limit() -> Result<()> {
+    let req = hyper::Request::builder()
+        .uri("http://localhost")
+        .body(body::full("Hello"))?;
+
+    let resp = run_wasi_http(test_programs_artifacts::SERVER_BODY_LIMIT_COMPONENT, req).await??;
+    assert_eq!(resp.status(), 200);
+
+    let req = hyper::Request::builder()
+        .uri("http://localhost")
+        .body(body::full("Hello, World!"))?;
+
+    let resp = run_wasi_http(test_programs_artifacts::SERVER_BODY_LIMIT_COMPONENT, req).await??;
+    assert_eq!(resp.status(), 413);
+
+    let req = hyper::Request::builder()
+        .uri("http://localhost")
+        .header("Content-Length", "1024")
+        .body(body::full("Hello"))?;
+
+    let resp = run_wasi_http(test_programs_artifacts::SERVER_BODY_LIMIT_COMPONENT, req).await??;
+    assert_eq!(resp.status(), 413);
+
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn multipart_form() -> Result<()> {
     let req = hyper::Request::builder()
@@ -62,6 +136,72 @@ async fn multipart_form() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn trailers() -> Result<()> {
+    let req = hyper::Request::builder()
+        .uri("http://localhost")
+        .body(body::full_with_trailers(
+            "Hello World",
+            [("checksum", "123")],
+        ))?;
+
+    let resp = run_wasi_http(test_programs_artifacts::SERVER_TRAILERS_COMPONENT, req).await??;
+    let body = resp.into_body();
+    assert_eq!(body.trailers().unwrap().get("checksum").unwrap(), "123");
+    let body = body.to_bytes();
+    let body = std::str::from_utf8(&body)?;
+    assert_eq!(body, "Hello World");
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn multi_headers() -> Result<()> {
+    let req = hyper::Request::builder()
+        .uri("http://localhost")
+        .header("X-Tag", "b")
+        .header("X-Tag", "a")
+        .header("X-Tag", "c")
+        .body(body::empty())?;
+
+    let resp =
+        run_wasi_http(test_programs_artifacts::SERVER_MULTI_HEADERS_COMPONENT, req).await??;
+    let headers = resp.headers();
+    assert_eq!(
+        headers.get_all("Set-Cookie").iter().collect::<Vec<_>>(),
+        ["a=1", "b=2"]
+    );
+    assert_eq!(
+        headers.get_all("Link").iter().collect::<Vec<_>>(),
+        ["</style.css>; rel=preload", "</app.js>; rel=preload"]
+    );
+    assert!(headers.get("X-Removed").is_none());
+    let body = resp.into_body().to_bytes();
+    let body = std::str::from_utf8(&body)?;
+    assert_eq!(body, "b a c");
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn multipart_reader() -> Result<()> {
+    let req = hyper::Request::builder()
+        .uri("http://localhost")
+        .header("Content-Type", "multipart/form-data; boundary=boundary")
+        .body(body::full("--boundary\r\nContent-Disposition: form-data; name=form\r\n\r\nHello \r\n--boundary\r\nContent-Disposition: form-data; name=file; filename=file.txt\r\nContent-Type: text/plain\r\n\r\nWorld\r\n--boundary--"))?;
+
+    let resp = run_wasi_http(
+        test_programs_artifacts::SERVER_MULTIPART_READER_COMPONENT,
+        req,
+    )
+    .await??;
+    let body = resp.into_body().to_bytes();
+    let body = std::str::from_utf8(&body)?;
+    assert_eq!(body, "Hello World");
+
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn query() -> Result<()> {
     let req = hyper::Request::builder()
@@ -76,6 +216,70 @@ async fn query() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn query_as() -> Result<()> {
+    let req = hyper::Request::builder()
+        .uri("http://localhost?name=ia&tag=a&tag=b&page=")
+        .body(body::empty())?;
+
+    let resp = run_wasi_http(test_programs_artifacts::SERVER_QUERY_AS_COMPONENT, req).await??;
+    let body = resp.into_body().to_bytes();
+    let body = std::str::from_utf8(&body)?;
+    assert_eq!(body, r#"ia ["a", "b"] 1"#);
+
+    let req = hyper::Request::builder()
+        .uri("http://localhost?name=ia&page=one")
+        .body(body::empty())?;
+
+    let resp = run_wasi_http(test_programs_artifacts::SERVER_QUERY_AS_COMPONENT, req).await??;
+    assert_eq!(resp.status(), 400);
+    let body = resp.into_body().to_bytes();
+    let body = std::str::from_utf8(&body)?;
+    assert_eq!(
+        body,
+        r#"invalid field `page`: invalid value "one": invalid digit found in string"#
+    );
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn negotiate() -> Result<()> {
+    let req = hyper::Request::builder()
+        .uri("http://localhost")
+        .header("Accept", "text/*;q=0.9, application/json;q=0.5")
+        .header("Accept-Language", "fr-CH, fr;q=0.9, en;q=0.8")
+        .body(body::empty())?;
+
+    let resp = run_wasi_http(test_programs_artifacts::SERVER_NEGOTIATE_COMPONENT, req).await??;
+    assert_eq!(resp.headers().get("Content-Type").unwrap(), "text/html");
+    assert_eq!(resp.headers().get("Content-Language").unwrap(), "fr");
+    let body = resp.into_body().to_bytes();
+    assert_eq!(body, "<p>Bonjour</p>");
+
+    let req = hyper::Request::builder()
+        .uri("http://localhost")
+        .body(body::empty())?;
+
+    let resp = run_wasi_http(test_programs_artifacts::SERVER_NEGOTIATE_COMPONENT, req).await??;
+    assert_eq!(
+        resp.headers().get("Content-Type").unwrap(),
+        "application/json"
+    );
+    let body = resp.into_body().to_bytes();
+    assert_eq!(body, r#"{"message": "Hello"}"#);
+
+    let req = hyper::Request::builder()
+        .uri("http://localhost")
+        .header("Accept", "image/png")
+        .body(body::empty())?;
+
+    let resp = run_wasi_http(test_programs_artifacts::SERVER_NEGOTIATE_COMPONENT, req).await??;
+    assert_eq!(resp.status(), 406);
+
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn status_code() -> Result<()> {
     let req = hyper::Request::builder()
@@ -94,7 +298,7 @@ async fn authority() -> Result<()> {
         .uri("http://127.0.0.1:3000")
         .body(body::empty())?;
 
-    let resp: http::Response<http_body_util::Collected<bytes::Bytes>> =
+    let resp: http::Response<http_body_util::Collected<hyper::body::Bytes>> =
         run_wasi_http(test_programs_artifacts::SERVER_AUTHORITY_COMPONENT, req).await??;
     let body = resp.into_body().to_bytes();
     let body = std::str::from_utf8(&body)?;
@@ -103,14 +307,102 @@ async fn authority() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn serve_dir() -> Result<()> {
+    let req = hyper::Request::builder()
+        .uri("http://localhost/static/style.css")
+        .body(body::empty())?;
+
+    let resp = run_wasi_http(test_programs_artifacts::SERVER_SERVE_DIR_COMPONENT, req).await??;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("Content-Type").unwrap(), "text/css");
+    let body = resp.into_body().to_bytes();
+    assert_eq!(body, "body { color: red; }\n");
+
+    let req = hyper::Request::builder()
+        .uri("http://localhost/static/docs")
+        .body(body::empty())?;
+
+    let resp = run_wasi_http(test_programs_artifacts::SERVER_SERVE_DIR_COMPONENT, req).await??;
+    assert_eq!(resp.status(), 301);
+    assert_eq!(resp.headers().get("Location").unwrap(), "./docs/");
+
+    let req = hyper::Request::builder()
+        .uri("http://localhost/static/docs/")
+        .body(body::empty())?;
+
+    let resp = run_wasi_http(test_programs_artifacts::SERVER_SERVE_DIR_COMPONENT, req).await??;
+    let body = resp.into_body().to_bytes();
+    let body = std::str::from_utf8(&body)?;
+    assert!(body.contains("<a href=\"guide.txt\">guide.txt</a>"));
+
+    let req = hyper::Request::builder()
+        .uri("http://localhost/static/../file.txt")
+        .body(body::empty())?;
+
+    let resp = run_wasi_http(test_programs_artifacts::SERVER_SERVE_DIR_COMPONENT, req).await??;
+    assert_eq!(resp.status(), 404);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn embed_dir() -> Result<()> {
+    let req = hyper::Request::builder()
+        .uri("http://localhost/static/")
+        .body(body::empty())?;
+
+    let resp = run_wasi_http(test_programs_artifacts::SERVER_EMBED_DIR_COMPONENT, req).await??;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("Content-Type").unwrap(), "text/html");
+    let body = resp.into_body().to_bytes();
+    assert_eq!(body, "<!DOCTYPE html>\n<h1>Hello</h1>\n");
+
+    let req = hyper::Request::builder()
+        .uri("http://localhost/static/style.css")
+        .header("Accept-Encoding", "gzip")
+        .body(body::empty())?;
+
+    let resp = run_wasi_http(test_programs_artifacts::SERVER_EMBED_DIR_COMPONENT, req).await??;
+    assert_eq!(resp.headers().get("Content-Type").unwrap(), "text/css");
+    assert_eq!(resp.headers().get("Content-Encoding").unwrap(), "gzip");
+    assert_eq!(resp.headers().get("Vary").unwrap(), "accept-encoding");
+
+    let req = hyper::Request::builder()
+        .uri("http://localhost/static/style.css")
+        .body(body::empty())?;
+
+    let resp = run_wasi_http(test_programs_artifacts::SERVER_EMBED_DIR_COMPONENT, req).await??;
+    assert!(resp.headers().get("Content-Encoding").is_none());
+    let body = resp.into_body().to_bytes();
+    assert_eq!(body, "body { color: red; }\n");
+
+    Ok(())
+}
+
 mod body {
     use http_body_util::{combinators::BoxBody, BodyExt, Empty, Full};
-    use hyper::{body::Bytes, Error};
+    use hyper::{body::Bytes, Error, HeaderMap};
 
     pub fn full(bytes: &'static str) -> BoxBody<Bytes, Error> {
         BoxBody::new(Full::new(bytes.into()).map_err(|_| unreachable!()))
     }
 
+    pub fn full_with_trailers<const N: usize>(
+        bytes: &'static str,
+        trailers: [(&'static str, &'static str); N],
+    ) -> BoxBody<Bytes, Error> {
+        let trailers = trailers
+            .into_iter()
+            .map(|(k, v)| (k.parse().unwrap(), v.parse().unwrap()))
+            .collect::<HeaderMap>();
+        BoxBody::new(
+            Full::new(bytes.into())
+                .map_err(|_| unreachable!())
+                .with_trailers(std::future::ready(Some(Ok(trailers)))),
+        )
+    }
+
     pub fn empty() -> BoxBody<Bytes, Error> {
         BoxBody::new(Empty::new().map_err(|_| unreachable!()))
     }